@@ -0,0 +1,50 @@
+use std::io::Cursor;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust_lexical_analyzer::{
+    langdef::LanguageDefinition,
+    lexer::Lexer,
+    tokenizer::Tokenizer,
+};
+
+/// A script of roughly ``size`` bytes, mixing statements, blocks,
+/// strings, numbers and comments
+fn script(size: usize) -> String {
+    let block: &str = concat!(
+        "/* Calculates the total */\n",
+        "let total = 0;\n",
+        "let name = \"John \\\"Doe\\\"\";\n",
+        "if (total >= 10 && name != \"\") {\n",
+        "    total += 0xFF * 2.5e-3 - 1_000; // Adjust\n",
+        "} else {\n",
+        "    print(name, total ** 2);\n",
+        "}\n",
+    );
+    block.repeat(size / block.len() + 1)
+}
+
+fn tokenize(c: &mut Criterion) {
+    let langdef: LanguageDefinition = LanguageDefinition::new(vec!["if", "else", "let", "print"]);
+    let mut group = c.benchmark_group("tokenize");
+
+    for size in [64 * 1024, 1024 * 1024] {
+        let document: String = script(size);
+        group.throughput(Throughput::Bytes(document.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("tokenizer", size), &document, |b, document| {
+            b.iter(|| Tokenizer::tokenize(&langdef, document).unwrap())
+        });
+
+        group.bench_with_input(BenchmarkId::new("lexer", size), &document, |b, document| {
+            b.iter(|| Lexer::new(&langdef, document).filter_map(Result::ok).count())
+        });
+
+        group.bench_with_input(BenchmarkId::new("reader", size), &document, |b, document| {
+            b.iter(|| Lexer::from_reader(&langdef, Cursor::new(document.as_bytes())).filter_map(Result::ok).count())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, tokenize);
+criterion_main!(benches);
@@ -1,11 +1,13 @@
 use crate::{
     langdef::LanguageDefinition,
+    span::Span,
     composer::{
         ComposerContext::*,
         BracketType::*,
+        ComposeError::*,
     },
     tokenizer::{
-        TokenStream,
+        Token,
         TokenType::{self, *},
     },
     fragments::{
@@ -16,34 +18,66 @@ use crate::{
     },
 };
 
-use std::fmt::Result;
-use core::fmt::{Debug, Formatter};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
 
 /// # Composition type
-/// Alias of ``Vec<Frg>`` for readability
-pub type Composition = Vec<Frg>;
-
-/// # Boxed Fragment trait
-/// Alias for the boxed ``Fragment`` trait
-pub type Frg = Box<dyn Fragment>;
+/// Alias of ``Vec<Fragment>`` for readability
+pub type Composition<'a> = Vec<Fragment<'a>>;
 
 /// # Composer context
 /// Indicates a context as we're iterating over the stream of tokens
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum ComposerContext {
     Closure(BracketType),
 }
 
 /// # Bracket type
 /// Enum for the different types of brackets, such as curly, square
-/// and parenthetical
-#[derive(Debug, PartialEq, Clone)]
+/// and parenthetical. ``Curly`` covers the block delimiters of the
+/// ``LanguageDefinition``, which are curly brackets by default, as well
+/// as blocks formed by indentation (``Indent`` and ``Dedent``).
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum BracketType {
     Square,
     Curly,
     Parenthetical,
 }
 
+/// # Compose error
+/// The errors which can occur while composing a token stream.
+/// The spans point to the offending brackets in the document.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ComposeError {
+    /// A bracket was opened, but never closed
+    UnclosedBracket { opener: Span },
+
+    /// A closing bracket was found without any open bracket
+    StrayCloser { closer: Span },
+
+    /// A bracket was closed by a bracket of a different type, for example ``( ... ]``
+    MismatchedBracket { opener: Span, closer: Span },
+}
+
+impl Display for ComposeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UnclosedBracket { opener } => write!(f, "Unclosed bracket opened at {}", opener.start),
+            StrayCloser { closer } => write!(f, "Unexpected closing bracket at {}", closer.start),
+            MismatchedBracket { opener, closer } => write!(
+                f,
+                "Bracket opened at {} doesn't match the bracket closing it at {}",
+                opener.start,
+                closer.start,
+            ),
+        }
+    }
+}
+
+impl Error for ComposeError {}
+
 /// # Composer
 /// The struct used to access the composing functions
 #[derive(Debug, Clone)]
@@ -55,27 +89,56 @@ impl Composer {
     /// returns a ``Composition`` struct which contains the hierarchical structure
     /// of the code. To the best of its ability it will also break
     /// the source code into statements.
-    pub fn compose(
+    ///
+    /// Unbalanced or mismatched brackets are reported as a ``ComposeError``.
+    ///
+    /// The fragments refer to ranges of the token stream, which is therefore
+    /// borrowed for as long as the composition lives.
+    ///
+    /// ``DocComment`` tokens are attached to the statement or encapsulation
+    /// following them, and can be found with ``Fragment::get_doc_comment``.
+    pub fn compose<'a>(
         langdef: &LanguageDefinition,
-        stream: &TokenStream,
-    ) -> Composition {
-        Self::process(&langdef, &stream)
+        stream: &'a [Token<'a>],
+    ) -> Result<Composition<'a>, ComposeError> {
+        Self::process(langdef, stream)
     }
 
     /// # Get context
-    /// Returns a ``ComposerContext`` based on the passed punctuator.
-    /// For example, it maps {...} punctuators to the ``Curly`` type.
+    /// Returns a ``ComposerContext`` based on the passed token, if it's a bracket.
+    /// The block delimiters of the ``LanguageDefinition`` (by default {...}) are
+    /// mapped to the ``Curly`` type.
     fn get_context(
-        punctuator: &TokenType,
-    ) -> ComposerContext {
-        match punctuator {
-            Punctuator('{') | Punctuator('}') => Closure(Curly),
-            Punctuator('(') | Punctuator(')') => Closure(Parenthetical),
-            Punctuator('[') | Punctuator(']') => Closure(Square),
-            _ => panic!("Not a closure type: {:?}", punctuator),
+        langdef: &LanguageDefinition,
+        token_type: &TokenType,
+    ) -> Option<ComposerContext> {
+        match token_type {
+            t if t == langdef.block_opener() || t == langdef.block_closer() => Some(Closure(Curly)),
+            Indent | Dedent => Some(Closure(Curly)),
+            Punctuator('(') | Punctuator(')') => Some(Closure(Parenthetical)),
+            Punctuator('[') | Punctuator(']') => Some(Closure(Square)),
+            _ => None,
         }
     }
 
+    /// # Is opener
+    /// Returns true, if the token opens an encapsulation
+    fn is_opener(
+        langdef: &LanguageDefinition,
+        token_type: &TokenType,
+    ) -> bool {
+        token_type == langdef.block_opener() || matches!(token_type, Punctuator('(') | Punctuator('[') | Indent)
+    }
+
+    /// # Is closer
+    /// Returns true, if the token closes an encapsulation
+    fn is_closer(
+        langdef: &LanguageDefinition,
+        token_type: &TokenType,
+    ) -> bool {
+        token_type == langdef.block_closer() || matches!(token_type, Punctuator(')') | Punctuator(']') | Dedent)
+    }
+
     /// # Process
     /// Take a token stream and language definition, and use those in conjunction
     /// to break a token stream into statements and encapsulations.
@@ -88,10 +151,10 @@ impl Composer {
     ///
     /// The composition can be used to more easily analyze and evaluate the grammar
     /// and hierarchical structure of the code.
-    fn process(
+    fn process<'a>(
         langdef: &LanguageDefinition,
-        stream: &TokenStream,
-    ) -> Composition {
+        stream: &'a [Token<'a>],
+    ) -> Result<Composition<'a>, ComposeError> {
         // The overall composition struct which will be returned in the end
         let mut composition: Composition = Composition::new();
 
@@ -99,145 +162,180 @@ impl Composer {
         // inside a bracket or parenthetisis
         let mut context: Option<ComposerContext> = None;
 
-        // Temporary container of tokens
-        let mut buffer: TokenStream = TokenStream::new();
+        // The tokens which are picked up, until we know what to do with them,
+        // always form a range of the stream. This is where the range begins.
+        let mut buffer: usize = 0;
+
+        // Indicates the indentations of nested closures, with their context
+        // and the span of the token opening them
+        let mut indentations: Vec<(ComposerContext, Span)> = vec![];
+
+        // The span of the punctuator which opened the current context
+        let mut opener: Span = Span::default();
 
-        // Indicates the indentations of nested closures and their context
-        let mut indentations: Vec<ComposerContext> = vec![];
+        // The doc comment waiting for the next statement or encapsulation
+        let mut doc_comment: Option<&'a Token<'a>> = None;
 
-        for token in stream {
+        for (index, token) in stream.iter().enumerate() {
             match (&token.token_type, &context) {
-                // If we reach the "end of statement" punctuator, we add the current
-                // buffer content to the output
-                (Punctuator(';'), None) => Self::add_stream(&mut composition, &mut buffer),
-
-                // If we reach the EOS punctuator inside a context, we add it to the buffer
-                (Punctuator(';'), _) => buffer.insert(buffer.len(), token.clone()),
-
-                // If we encounter a bracket-style punctuator we open a new context
-                (Punctuator('{'), None)
-                | (Punctuator('('), None)
-                | (Punctuator('['), None) => {
-                    Self::add_stream(&mut composition, &mut buffer);
-                    context = Some(Self::get_context(&token.token_type));
+                // If we reach the "end of statement" token, we add the current
+                // buffer content to the output. A doc comment which is still waiting
+                // (in front of a single token, or nothing at all) documents nothing,
+                // unless it's on a line of its own, ended by a newline terminator
+                (t, None) if t == langdef.statement_terminator() => {
+                    Self::add_stream(&mut composition, &stream[buffer..index], &mut doc_comment);
+                    Self::terminate(&mut composition);
+                    if buffer < index || *t != Newline {
+                        doc_comment = None;
+                    }
+                    buffer = index + 1;
+                },
+
+                // Doc comments in front of a fragment are kept aside, until the fragment
+                // they document is added. Inside a context they're handled when composing
+                // its contents, and in the middle of a statement they're kept in the buffer
+                (DocComment(_), None) if buffer == index => {
+                    buffer = index + 1;
+                    doc_comment = Some(token);
                 },
 
-                // If we encounter a bracket-style punctuator while inside a context,
-                // we we just add it to the buffer and indicate in the indentation
-                (Punctuator('{'), Some(Closure(_)))
-                | (Punctuator('('), Some(Closure(_)))
-                | (Punctuator('['), Some(Closure(_))) => {
-                    buffer.insert(buffer.len(), token.clone());
-                    indentations.insert(indentations.len(), context.clone().unwrap());
+                // Line breaks found along with another terminator (with significant
+                // indentation) end the statement as well, without being part of it.
+                // Where the statement continues, the tokenizer leaves them out.
+                (Newline, None) => {
+                    Self::add_stream(&mut composition, &stream[buffer..index], &mut doc_comment);
+                    Self::terminate(&mut composition);
+                    buffer = index + 1;
                 },
 
-                // When reaching the closing punctuator, we insert the obtained buffer
+                // If we reach the EOS token inside a context, we keep it in the buffer
+                (t, _) if t == langdef.statement_terminator() => {},
+
+                // If we encounter a bracket-style token we open a new context
+                (t, None) if Self::is_opener(langdef, t) => {
+                    Self::add_stream(&mut composition, &stream[buffer..index], &mut doc_comment);
+                    buffer = index + 1;
+                    context = Self::get_context(langdef, t);
+                    opener = token.span;
+                },
+
+                // If we encounter a bracket-style token while inside a context,
+                // we keep it in the buffer and indicate in the indentation
+                (t, Some(Closure(_))) if Self::is_opener(langdef, t) => {
+                    indentations.insert(indentations.len(), (Self::get_context(langdef, t).unwrap(), token.span));
+                },
+
+                // When reaching the closing token, we insert the obtained buffer
                 // content in the output.
-                // We recursively handle nested closures, before adding from this layer
-                (Punctuator('}'), Some(Closure(_)))
-                | (Punctuator(')'), Some(Closure(_)))
-                | (Punctuator(']'), Some(Closure(_))) => {
-                    if indentations.len() > 0 {
-                        buffer.insert(buffer.len(), token.clone());
+                // We recursively handle nested closures, before adding from this layer.
+                // The closing token must be of the same type as the one it closes.
+                (t, Some(current)) if Self::is_closer(langdef, t) => {
+                    let (expected, opened_at) = indentations.last().cloned().unwrap_or((current.clone(), opener));
+                    if Self::get_context(langdef, t).as_ref() != Some(&expected) {
+                        return Err(MismatchedBracket { opener: opened_at, closer: token.span });
+                    }
+
+                    if !indentations.is_empty() {
                         indentations.remove(indentations.len() - 1);
                     } else {
-                        Self::add_encapsulation(&langdef, &mut composition, &mut buffer, &mut context);
+                        let span: Span = opener.join(&token.span);
+                        Self::add_encapsulation(langdef, &mut composition, &stream[buffer..index], &mut context, span, &mut doc_comment)?;
+                        buffer = index + 1;
                     }
                 },
 
-                // When none of the above actions are reached, we simply add the token
-                // to the buffer
-                _ => buffer.insert(buffer.len(), token.clone()),
+                // A closing token outside any context has nothing to close
+                (t, None) if Self::is_closer(langdef, t) => {
+                    return Err(StrayCloser { closer: token.span });
+                },
+
+                // When none of the above actions are reached, we simply
+                // keep the token in the buffer
+                _ => {},
             }
         }
 
+        // Reaching the end while still inside a context means (at least)
+        // the innermost bracket was never closed
+        if context.is_some() {
+            let opened_at: Span = indentations.last().map(|(_, span)| *span).unwrap_or(opener);
+            return Err(UnclosedBracket { opener: opened_at });
+        }
+
         // If the buffer holds content, it indicates no token indicated
         // an end of the statement. This is for example seen in nested
         // parenthetical expression such as A + (B + (C + D))
-        if buffer.len() > 0 {
-            Self::add_stream(&mut composition, &mut buffer);
-        }
+        Self::add_stream(&mut composition, &stream[buffer..], &mut doc_comment);
 
-        composition
+        Ok(composition)
     }
 
     /// # Add encapsulation
-    /// Handle the composition, buffer and context
-    /// when we trigger the adding of an encapsulation to the composition
-    fn add_encapsulation(
+    /// Handle the composition and context
+    /// when we trigger the adding of an encapsulation to the composition.
+    /// The ``span`` covers the brackets of the encapsulation.
+    fn add_encapsulation<'a>(
         langdef: &LanguageDefinition,
-        composition: &mut Composition,
-        buffer: &mut TokenStream,
+        composition: &mut Composition<'a>,
+        tokens: &'a [Token<'a>],
         context: &mut Option<ComposerContext>,
-    ) {
+        span: Span,
+        doc_comment: &mut Option<&'a Token<'a>>,
+    ) -> Result<(), ComposeError> {
         composition.insert(
             composition.len(),
-            Box::new(Encapsulation {
+            Fragment::Encapsulation(Encapsulation {
                 context: context.as_ref().unwrap().clone(),
-                composition: Self::process(&langdef, &buffer),
+                composition: Self::process(langdef, tokens)?,
+                span,
+                doc_comment: doc_comment.take(),
+                terminated: false,
             }),
         );
-        *buffer = TokenStream::new();
         *context = None;
+
+        Ok(())
     }
 
     /// # Add token stream to composition
-    /// Add the current token stream (granted it has at least one item)
+    /// Add the tokens (granted there's at least one)
     /// to the composition
-    fn add_stream(
-        composition: &mut Composition,
-        stream: &mut TokenStream,
+    fn add_stream<'a>(
+        composition: &mut Composition<'a>,
+        tokens: &'a [Token<'a>],
+        doc_comment: &mut Option<&'a Token<'a>>,
     ) {
-        if stream.len() > 0 {
+        if !tokens.is_empty() {
             composition.insert(
                 composition.len(),
-                Self::create_fragment(&stream),
+                Self::create_fragment(tokens, doc_comment),
             );
         }
-        *stream = TokenStream::new();
     }
 
     /// # Create fragment
     /// Returns the correct Fragment type based on the
-    /// number of elements in the token stream
-    fn create_fragment(
-        stream: &TokenStream,
-    ) -> Box<dyn Fragment> {
-        match stream.len() {
-            1 => Box::new(SingleToken {
-                token: stream.get(0).unwrap().clone(),
-            }),
-            _ => Box::new(Statement {
-                token_stream: stream.clone(),
-            })
+    /// number of tokens. Statements take the waiting doc comment,
+    /// while single tokens leave it for the next fragment.
+    fn create_fragment<'a>(
+        tokens: &'a [Token<'a>],
+        doc_comment: &mut Option<&'a Token<'a>>,
+    ) -> Fragment<'a> {
+        match tokens {
+            [token] => Fragment::SingleToken(SingleToken { token, terminated: false }),
+            _ => Fragment::Statement(Statement { tokens, doc_comment: doc_comment.take(), terminated: false }),
         }
     }
-}
-
-impl Debug for dyn Fragment {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{}", self.debug())
-    }
-}
 
-// Implementation of Clone trait for ``dyn Fragment`` which cannot
-// derive Rust's native ``Clone`` trait due to unknown memory size of ``Box``
-//
-// @todo There's plenty of room for improvement in this code
-impl Clone for Box<dyn Fragment> {
-    fn clone(&self) -> Box<dyn Fragment> {
-        match self.get_type() {
-            "single_token" => Box::new(SingleToken {
-                token: self.get_token().unwrap().clone(),
-            }),
-            "block" => Box::new(Encapsulation {
-                context: self.get_context().unwrap(),
-                composition: self.get_fragments().unwrap(),
-            }),
-            "statement" => Box::new(Statement {
-                token_stream: self.get_tokens().clone(),
-            }),
-            _ => panic!("Unknown implementation in Clone for Box<dyn Fragment>"),
+    /// # Terminate
+    /// Mark the last fragment of the composition as ended by the statement
+    /// terminator, which tells it apart from a statement continuing after an encapsulation
+    fn terminate(composition: &mut Composition) {
+        match composition.last_mut() {
+            Some(Fragment::Statement(statement)) => statement.terminated = true,
+            Some(Fragment::SingleToken(single)) => single.terminated = true,
+            Some(Fragment::Encapsulation(encapsulation)) => encapsulation.terminated = true,
+            None => {},
         }
     }
 }
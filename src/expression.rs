@@ -0,0 +1,304 @@
+use crate::{
+    composer::{BracketType::Parenthetical, ComposerContext::Closure},
+    expression::ExpressionError::*,
+    fragments::{Encapsulation, Fragment},
+    langdef::{Associativity, LanguageDefinition},
+    span::{Position, Span},
+    tokenizer::{Token, TokenType},
+};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// # Expression
+/// A typed expression tree, built by the ``ExpressionParser``.
+/// It refers to the tokens it was built from (``'a``).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expression<'a> {
+    /// A literal, such as ``30`` or ``"Hello world"``
+    Literal(&'a Token<'a>),
+
+    /// An identifier, such as the name of a variable
+    Identifier(&'a Token<'a>),
+
+    /// An operator placed in front of its operand, for example ``-x``
+    Prefix { operator: &'a Token<'a>, operand: Box<Expression<'a>> },
+
+    /// An operator placed between its operands, for example ``a + b``
+    Infix { operator: &'a Token<'a>, left: Box<Expression<'a>>, right: Box<Expression<'a>> },
+
+    /// An operator placed after its operand, for example ``x!``
+    Postfix { operator: &'a Token<'a>, operand: Box<Expression<'a>> },
+
+    /// An expression wrapped in parentheses. The span covers the parentheses.
+    Group { expression: Box<Expression<'a>>, span: Span },
+}
+
+impl Expression<'_> {
+    /// # Get span
+    /// Returns the area of the document covered by the expression
+    pub fn get_span(&self) -> Span {
+        match self {
+            Expression::Literal(token) | Expression::Identifier(token) => token.span,
+            Expression::Prefix { operator, operand } => operator.span.join(&operand.get_span()),
+            Expression::Infix { left, right, .. } => left.get_span().join(&right.get_span()),
+            Expression::Postfix { operator, operand } => operand.get_span().join(&operator.span),
+            Expression::Group { span, .. } => *span,
+        }
+    }
+}
+
+/// Expressions are displayed in prefix notation, which makes their
+/// structure visible, for example ``(+ 1 (* 2 3))``
+impl Display for Expression<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let text = |token: &Token| match &token.token_type {
+            TokenType::Literal(_, value)
+            | TokenType::Identifier(value)
+            | TokenType::Operator(value)
+            | TokenType::Keyword(value) => value.to_string(),
+            other => format!("{:?}", other),
+        };
+
+        match self {
+            Expression::Literal(token) | Expression::Identifier(token) => write!(f, "{}", text(token)),
+            Expression::Prefix { operator, operand } => write!(f, "({} {})", text(operator), operand),
+            Expression::Infix { operator, left, right } => write!(f, "({} {} {})", text(operator), left, right),
+            Expression::Postfix { operator, operand } => write!(f, "({} {})", text(operator), operand),
+            Expression::Group { expression, .. } => write!(f, "{}", expression),
+        }
+    }
+}
+
+/// # Expression error
+/// The errors which can occur while parsing an expression
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExpressionError {
+    /// A token which can't be placed where it was found
+    UnexpectedToken { token: Token<'static> },
+
+    /// An encapsulation which isn't parenthetical, such as ``[...]`` or ``{...}``
+    UnexpectedBlock { span: Span },
+
+    /// The expression ended, while an operand was still expected after ``span``
+    UnexpectedEnd { span: Span },
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UnexpectedToken { token } => write!(f, "Unexpected {:?} at {}", token.token_type, token.span.start),
+            UnexpectedBlock { span } => write!(f, "Unexpected block at {}", span.start),
+            UnexpectedEnd { span } => write!(f, "Expression ends unexpectedly at {}", span.end),
+        }
+    }
+}
+
+impl Error for ExpressionError {}
+
+/// # Item
+/// The parser sees an expression as a series of tokens and
+/// encapsulations (which are expected to be parenthetical)
+#[derive(Debug, Clone, Copy)]
+enum Item<'a> {
+    Token(&'a Token<'a>),
+    Group(&'a Encapsulation<'a>),
+}
+
+/// # Expression parser
+/// A Pratt parser building an ``Expression`` tree from tokens and fragments,
+/// using the prefix, infix and postfix operator tables of the ``LanguageDefinition``
+#[derive(Debug, Clone)]
+pub struct ExpressionParser;
+
+impl ExpressionParser {
+    /// # Parse
+    /// Build an ``Expression`` from a series of fragments. Since the composer
+    /// breaks statements at parentheses, ``1 + (2 * 3)`` consists of a statement
+    /// and an encapsulation, which are parsed as one expression.
+    pub fn parse<'a>(
+        langdef: &LanguageDefinition,
+        fragments: &'a [Fragment<'a>],
+    ) -> Result<Expression<'a>, ExpressionError> {
+        Self::parse_items(langdef, &Self::items_of(fragments), Span::default())
+    }
+
+    /// # Parse fragment
+    /// Build an ``Expression`` from a single fragment. For encapsulations,
+    /// the expression is built from their contents.
+    pub fn parse_fragment<'a>(
+        langdef: &LanguageDefinition,
+        fragment: &'a Fragment<'a>,
+    ) -> Result<Expression<'a>, ExpressionError> {
+        match fragment {
+            Fragment::Encapsulation(encapsulation) => Self::parse_group(langdef, encapsulation),
+            _ => Self::parse(langdef, std::slice::from_ref(fragment)),
+        }
+    }
+
+    /// # Parse tokens
+    /// Build an ``Expression`` from a series of tokens
+    pub fn parse_tokens<'a>(
+        langdef: &LanguageDefinition,
+        tokens: &'a [Token<'a>],
+    ) -> Result<Expression<'a>, ExpressionError> {
+        Self::parse_items(langdef, &Self::items_of_tokens(tokens), Span::default())
+    }
+
+    /// # Parse continued
+    /// Build an ``Expression`` from the rest of a statement, and the fragments
+    /// continuing it, such as captured by the ``Expression`` pattern of a ``Grammar``
+    pub fn parse_continued<'a>(
+        langdef: &LanguageDefinition,
+        tokens: &'a [Token<'a>],
+        fragments: &'a [Fragment<'a>],
+    ) -> Result<Expression<'a>, ExpressionError> {
+        let mut items: Vec<Item> = Self::items_of_tokens(tokens);
+        items.extend(Self::items_of(fragments));
+        Self::parse_items(langdef, &items, Span::default())
+    }
+
+    /// # Parse group
+    /// Build an ``Expression`` from the contents of an encapsulation
+    fn parse_group<'a>(
+        langdef: &LanguageDefinition,
+        encapsulation: &'a Encapsulation<'a>,
+    ) -> Result<Expression<'a>, ExpressionError> {
+        // An empty group ends right after the opening bracket (a single character)
+        let mut end: Position = encapsulation.span.start;
+        end.advance('(');
+        let opening: Span = Span::new(encapsulation.span.start, end);
+        Self::parse_items(langdef, &Self::items_of(&encapsulation.composition), opening)
+    }
+
+    /// # Items of
+    /// Flatten the fragments into the tokens of statements and single tokens,
+    /// and the encapsulations
+    fn items_of<'a>(fragments: &'a [Fragment<'a>]) -> Vec<Item<'a>> {
+        let mut items: Vec<Item> = vec![];
+
+        for fragment in fragments {
+            match fragment {
+                Fragment::Statement(statement) => items.extend(Self::items_of_tokens(statement.tokens)),
+                Fragment::SingleToken(single) => items.insert(items.len(), Item::Token(single.token)),
+                Fragment::Encapsulation(encapsulation) => items.insert(items.len(), Item::Group(encapsulation)),
+            }
+        }
+
+        items
+    }
+
+    /// # Items of tokens
+    /// Turn the tokens into items. Doc comments found in the middle
+    /// of a statement document nothing, and are left out.
+    fn items_of_tokens<'a>(tokens: &'a [Token<'a>]) -> Vec<Item<'a>> {
+        tokens
+            .iter()
+            .filter(|token| !matches!(token.token_type, TokenType::DocComment(_)))
+            .map(Item::Token)
+            .collect()
+    }
+
+    /// # Parse items
+    /// Parse all of the items as one expression. The ``before`` span is
+    /// reported if there are no items at all.
+    fn parse_items<'a>(
+        langdef: &LanguageDefinition,
+        items: &[Item<'a>],
+        before: Span,
+    ) -> Result<Expression<'a>, ExpressionError> {
+        let mut index: usize = 0;
+        let expression: Expression = Self::expression(langdef, items, &mut index, 0, before)?;
+
+        // Everything must be part of the expression
+        match items.get(index) {
+            Some(Item::Token(token)) => Err(UnexpectedToken { token: (*token).clone().into_owned() }),
+            Some(Item::Group(encapsulation)) => Err(UnexpectedBlock { span: encapsulation.span }),
+            None => Ok(expression),
+        }
+    }
+
+    /// # Expression
+    /// The core of the Pratt parser. Parses an operand (including prefix operators),
+    /// and then keeps applying postfix and infix operators, as long as they bind
+    /// at least as tightly as ``minimum``.
+    fn expression<'a>(
+        langdef: &LanguageDefinition,
+        items: &[Item<'a>],
+        index: &mut usize,
+        minimum: u16,
+        before: Span,
+    ) -> Result<Expression<'a>, ExpressionError> {
+        let item: Item = *items.get(*index).ok_or(UnexpectedEnd { span: before })?;
+        *index += 1;
+
+        let mut left: Expression = match item {
+            Item::Group(encapsulation) if encapsulation.context == Closure(Parenthetical) => Expression::Group {
+                expression: Box::new(Self::parse_group(langdef, encapsulation)?),
+                span: encapsulation.span,
+            },
+            Item::Group(encapsulation) => return Err(UnexpectedBlock { span: encapsulation.span }),
+            Item::Token(token) => match &token.token_type {
+                TokenType::Literal(..) => Expression::Literal(token),
+                TokenType::Identifier(_) => Expression::Identifier(token),
+                TokenType::Operator(operator) => {
+                    let power: u8 = langdef
+                        .prefix_binding_power(operator)
+                        .ok_or_else(|| UnexpectedToken { token: token.clone().into_owned() })?;
+                    Expression::Prefix {
+                        operator: token,
+                        operand: Box::new(Self::expression(langdef, items, index, power as u16, token.span)?),
+                    }
+                },
+                _ => return Err(UnexpectedToken { token: token.clone().into_owned() }),
+            },
+        };
+
+        loop {
+            let (token, operator) = match items.get(*index) {
+                None => break,
+                Some(Item::Token(token)) => match &token.token_type {
+                    TokenType::Operator(operator) => (*token, operator),
+                    _ => return Err(UnexpectedToken { token: (*token).clone().into_owned() }),
+                },
+                Some(Item::Group(encapsulation)) => return Err(UnexpectedBlock { span: encapsulation.span }),
+            };
+
+            if let Some(power) = langdef.postfix_binding_power(operator) {
+                if (power as u16) < minimum {
+                    break;
+                }
+                *index += 1;
+                left = Expression::Postfix {
+                    operator: token,
+                    operand: Box::new(left),
+                };
+                continue;
+            }
+
+            let (power, associativity) = langdef
+                .infix_binding_power(operator)
+                .ok_or_else(|| UnexpectedToken { token: token.clone().into_owned() })?;
+            if (power as u16) < minimum {
+                break;
+            }
+            *index += 1;
+
+            // Left associative operators require the right-hand side to bind tighter,
+            // so the next operator of the same power ends up on the outside
+            let next: u16 = match associativity {
+                Associativity::Left => power as u16 + 1,
+                Associativity::Right => power as u16,
+            };
+
+            left = Expression::Infix {
+                operator: token,
+                left: Box::new(left),
+                right: Box::new(Self::expression(langdef, items, index, next, token.span)?),
+            };
+        }
+
+        Ok(left)
+    }
+}
@@ -1,19 +1,128 @@
 use std::ops::Add;
+use core::fmt::{Debug, Formatter, Result};
 use crate::{
     composer::{ComposerContext, Composition},
-    tokenizer::{Token, TokenStream, TokenType::*},
+    span::Span,
+    tokenizer::{Token, TokenType::*},
 };
 
-/// # Fragment trait
-/// Any fragment which can be found and broken down to in the source code,
-/// must implement this ``Fragment`` trait
-pub trait Fragment {
-    fn get_type(&self) -> &'static str;
-    fn debug(&self) -> String;
-    fn get_context(&self) -> Option<ComposerContext>;
-    fn get_token(&self) -> Option<Token>;
-    fn get_tokens(&self) -> TokenStream;
-    fn get_fragments(&self) -> Option<Composition>;
+/// # Fragment
+/// Any fragment which can be found and broken down to in the source code.
+/// A fragment is either a ``Statement``, a ``SingleToken`` or an ``Encapsulation``,
+/// which can be told apart by matching on the enum.
+///
+/// Fragments refer to the tokens of the ``TokenStream`` they were composed from (``'a``),
+/// rather than holding copies of them.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Fragment<'a> {
+    Statement(Statement<'a>),
+    SingleToken(SingleToken<'a>),
+    Encapsulation(Encapsulation<'a>),
+}
+
+impl<'a> Fragment<'a> {
+    /// # Debug
+    /// Returns a human-readable representation of the fragment
+    // @todo: Room for improvement in this code
+    pub fn debug(&self) -> String {
+        match self {
+            Fragment::Statement(statement) => {
+                let mut str = String::new();
+                for token in statement.tokens {
+                    if !str.is_empty() {
+                        str = str.add( " ");
+                    }
+                    match &token.token_type {
+                        Literal(_, a) => str = str.add(format!("Literal({})", a).as_str()),
+                        Operator(a) | Keyword(a) => str = str.add(a),
+                        Identifier(a) => str = str.add(format!("Identifier{}", a).as_str()),
+                        _ => str = str.add(format!("{:?}", token).as_str()),
+                    }
+                }
+                format!("Statement: {}", str)
+            },
+            Fragment::SingleToken(single) => format!("{:?}", single.token.token_type),
+            Fragment::Encapsulation(encapsulation) => format!("Encapsulation: {:?}", encapsulation.composition),
+        }
+    }
+
+    /// # Get context
+    /// Returns the ``ComposerContext`` of encapsulations
+    pub fn get_context(&self) -> Option<ComposerContext> {
+        match self {
+            Fragment::Encapsulation(encapsulation) => Some(encapsulation.context.clone()),
+            _ => None,
+        }
+    }
+
+    /// # Get token
+    /// Returns the token of single token fragments
+    pub fn get_token(&self) -> Option<&'a Token<'a>> {
+        match self {
+            Fragment::SingleToken(single) => Some(single.token),
+            _ => None,
+        }
+    }
+
+    /// # Get tokens
+    /// Returns the tokens of statements. Other fragments return an empty slice.
+    pub fn get_tokens(&self) -> &'a [Token<'a>] {
+        match self {
+            Fragment::Statement(statement) => statement.tokens,
+            _ => &[],
+        }
+    }
+
+    /// # Get doc comment
+    /// Returns the ``DocComment`` token written right before statements
+    /// and encapsulations, such as ``/** Prints the value */``
+    pub fn get_doc_comment(&self) -> Option<&'a Token<'a>> {
+        match self {
+            Fragment::Statement(statement) => statement.doc_comment,
+            Fragment::Encapsulation(encapsulation) => encapsulation.doc_comment,
+            Fragment::SingleToken(_) => None,
+        }
+    }
+
+    /// # Get fragments
+    /// Returns the composition found inside encapsulations
+    pub fn get_fragments(&self) -> Option<&Composition<'a>> {
+        match self {
+            Fragment::Encapsulation(encapsulation) => Some(&encapsulation.composition),
+            _ => None,
+        }
+    }
+
+    /// # Is terminated
+    /// Whether the statement terminator was found right after the fragment.
+    /// Otherwise, the statement continues in the next fragment, as in ``(1 + 2) * 3``.
+    pub fn is_terminated(&self) -> bool {
+        match self {
+            Fragment::Statement(statement) => statement.terminated,
+            Fragment::SingleToken(single) => single.terminated,
+            Fragment::Encapsulation(encapsulation) => encapsulation.terminated,
+        }
+    }
+
+    /// # Get span
+    /// Returns the area of the document covered by the fragment
+    pub fn get_span(&self) -> Span {
+        match self {
+            Fragment::Statement(statement) => {
+                let first: Span = statement.tokens.first().map(|token| token.span).unwrap_or_default();
+                let last: Span = statement.tokens.last().map(|token| token.span).unwrap_or_default();
+                first.join(&last)
+            },
+            Fragment::SingleToken(single) => single.token.span,
+            Fragment::Encapsulation(encapsulation) => encapsulation.span,
+        }
+    }
+}
+
+impl Debug for Fragment<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.debug())
+    }
 }
 
 /// # Statement (fragment)
@@ -28,101 +137,38 @@ pub trait Fragment {
 /// ``if (x == 2)`` will not be considered a single statement (because of the parenthesis),
 /// but instead on the form ``if [encapsulation]``, and then the encapsulation contains
 /// a statement, ``x == 2``, which can be more conveniently evaluated (first)
-#[derive(Debug)]
-pub struct Statement {
-    pub token_stream: TokenStream,
-}
-
-impl Fragment for Statement {
-    fn get_type(&self) -> &'static str {
-        "statement"
-    }
-
-    // @todo: Room for improvement in this code
-    fn debug(&self) -> String {
-        let mut str = String::new();
-        for token in self.get_tokens() {
-            if !str.is_empty() {
-                str = str.add( " ");
-            }
-            match token.token_type {
-                Literal(a) => str = str.add(format!("Literal({})", a).as_str()),
-                Operator(a) | Keyword(a) => str = str.add(format!("{}", a).as_str()),
-                Identifier(a) => str = str.add(format!("Identifier{}", a).as_str()),
-                _ => str = str.add(format!("{:?}", token).as_str()),
-            }
-        }
-        format!("Statement: {}", str)
-    }
-    fn get_context(&self) -> Option<ComposerContext> {
-        None
-    }
-    fn get_token(&self) -> Option<Token> {
-        None
-    }
-    fn get_tokens(&self) -> TokenStream {
-        self.token_stream.clone()
-    }
-    fn get_fragments(&self) -> Option<Composition> {
-        None
-    }
+///
+/// The ``DocComment`` written right before the statement is kept in ``doc_comment``,
+/// rather than among the tokens. A doc comment in the middle of the statement
+/// remains among its tokens, but documents nothing.
+///
+/// ``terminated`` tells whether the statement terminator ended the statement,
+/// rather than the opening bracket of an encapsulation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Statement<'a> {
+    pub tokens: &'a [Token<'a>],
+    pub doc_comment: Option<&'a Token<'a>>,
+    pub terminated: bool,
 }
 
 /// # Single token (fragment)
 /// Contains a lone token which exists between statements and encapsulations.
 /// This is for example commonly seen with ``else`` keywords.
-#[derive(Debug)]
-pub struct SingleToken {
-    pub token: Token,
-}
-
-impl Fragment for SingleToken {
-    fn get_type(&self) -> &'static str {
-        "single_token"
-    }
-    fn debug(&self) -> String {
-        format!("{:?}", self.token.token_type)
-    }
-    fn get_context(&self) -> Option<ComposerContext> {
-        None
-    }
-    fn get_token(&self) -> Option<Token> {
-        Some(self.token.clone())
-    }
-    fn get_tokens(&self) -> TokenStream {
-        TokenStream::new()
-    }
-    fn get_fragments(&self) -> Option<Composition> {
-        None
-    }
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SingleToken<'a> {
+    pub token: &'a Token<'a>,
+    pub terminated: bool,
 }
 
 /// # Encapsulation (fragment)
 /// Indicates a body of multiple statements, single tokens and other
 /// encapsulations contained within a bracket such as (), [] or {}.
-#[derive(Debug)]
-pub struct Encapsulation {
+/// The span covers the brackets themselves as well as the contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Encapsulation<'a> {
     pub context: ComposerContext,
-    pub composition: Composition,
-}
-
-impl Fragment for Encapsulation {
-    fn get_type(&self) -> &'static str {
-        "block"
-    }
-    fn debug(&self) -> String {
-        format!("Encapsulation: {:?}", self.composition)
-    }
-    fn get_context(&self) -> Option<ComposerContext> {
-        Some(self.context.clone())
-    }
-    fn get_token(&self) -> Option<Token> {
-        None
-    }
-    fn get_tokens(&self) -> TokenStream {
-        TokenStream::new()
-    }
-    fn get_fragments(&self) -> Option<Composition> {
-        Some(self.composition.clone())
-    }
+    pub composition: Composition<'a>,
+    pub span: Span,
+    pub doc_comment: Option<&'a Token<'a>>,
+    pub terminated: bool,
 }
@@ -0,0 +1,353 @@
+use crate::{
+    composer::{BracketType, Composition, ComposerContext::Closure},
+    fragments::{Encapsulation, Fragment},
+    grammar::{GrammarError::*, Pattern::*},
+    span::Span,
+    tokenizer::{Token, TokenType},
+};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// # Handler
+/// The closure invoked when a rule is matched. It receives the ``Grammar``
+/// itself (so nested encapsulations can be run through it) and the ``RuleMatch``.
+pub type Handler<'a, T> = Box<dyn Fn(&Grammar<'a, T>, &RuleMatch<'_>) -> T + 'a>;
+
+/// # Pattern
+/// A single element of a rule, which is matched against the tokens of
+/// statements and single tokens, or against encapsulations
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// Exactly this token, for example ``Token(Keyword("let".into()))``
+    Token(TokenType<'static>),
+
+    /// Any keyword
+    AnyKeyword,
+
+    /// Any identifier
+    AnyIdentifier,
+
+    /// Any literal
+    AnyLiteral,
+
+    /// Any operator
+    AnyOperator,
+
+    /// Any punctuator
+    AnyPunctuator,
+
+    /// Any single token
+    AnyToken,
+
+    /// One or more tokens, covering the rest of the statement (``<expr>``).
+    /// When the statement continues after an encapsulation, as in ``(1 + 2) * 3``,
+    /// the expression covers the following fragments as well, up to a block
+    /// in curly brackets.
+    Expression,
+
+    /// An encapsulation, optionally of a specific bracket type.
+    /// The statement preceding it must have been fully matched.
+    Block(Option<BracketType>),
+}
+
+/// # Capture
+/// What a single ``Pattern`` matched in the composition (``'c``).
+/// An ``Expression`` consists of the rest of the statement it begins in,
+/// and the fragments continuing the statement.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Capture<'c> {
+    Token(&'c Token<'c>),
+    Expression(&'c [Token<'c>], &'c [Fragment<'c>]),
+    Block(&'c Encapsulation<'c>),
+}
+
+/// # Rule match
+/// Handed to the handler of a matched rule. It holds a ``Capture`` for each
+/// pattern of the rule (in the same order), and the span of the matched fragments.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuleMatch<'c> {
+    pub captures: Vec<Capture<'c>>,
+    pub span: Span,
+}
+
+impl<'c> RuleMatch<'c> {
+    /// # Token
+    /// Returns the token captured by the pattern at ``index``
+    pub fn token(&self, index: usize) -> Option<&'c Token<'c>> {
+        match self.captures.get(index) {
+            Some(Capture::Token(token)) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// # Tokens
+    /// Returns the tokens captured by the ``Expression`` pattern at ``index``,
+    /// when the expression doesn't continue past the statement it begins in
+    pub fn tokens(&self, index: usize) -> Option<&'c [Token<'c>]> {
+        match self.captures.get(index) {
+            Some(Capture::Expression(tokens, [])) => Some(tokens),
+            _ => None,
+        }
+    }
+
+    /// # Expression
+    /// Returns the rest of the statement and the following fragments captured
+    /// by the ``Expression`` pattern at ``index``. They can be parsed with
+    /// ``ExpressionParser::parse_continued``.
+    pub fn expression(&self, index: usize) -> Option<(&'c [Token<'c>], &'c [Fragment<'c>])> {
+        match self.captures.get(index) {
+            Some(Capture::Expression(tokens, fragments)) => Some((tokens, fragments)),
+            _ => None,
+        }
+    }
+
+    /// # Block
+    /// Returns the encapsulation captured by the ``Block`` pattern at ``index``
+    pub fn block(&self, index: usize) -> Option<&'c Encapsulation<'c>> {
+        match self.captures.get(index) {
+            Some(Capture::Block(encapsulation)) => Some(encapsulation),
+            _ => None,
+        }
+    }
+}
+
+/// # Grammar error
+/// The errors which can occur while running a composition through a ``Grammar``
+#[derive(Debug, PartialEq, Clone)]
+pub enum GrammarError {
+    /// None of the rules matched the fragment covering ``span``
+    NoRuleMatched { span: Span },
+}
+
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NoRuleMatched { span } => write!(f, "No grammar rule matches the code at {}", span.start),
+        }
+    }
+}
+
+impl Error for GrammarError {}
+
+/// # Rule
+/// A series of patterns and the handler to invoke when they're matched
+struct Rule<'a, T> {
+    patterns: Vec<Pattern>,
+    handler: Handler<'a, T>,
+}
+
+/// # Grammar
+/// Maps series of tokens and fragments to handlers.
+/// Rules are declared with ``with_rule``, and tried in the order they were declared.
+/// The handlers return a value of type ``T``, which is collected by ``run``.
+pub struct Grammar<'a, T> {
+    rules: Vec<Rule<'a, T>>,
+}
+
+impl<'a, T> Default for Grammar<'a, T> {
+    fn default() -> Grammar<'a, T> {
+        Grammar::new()
+    }
+}
+
+impl<'a, T> Grammar<'a, T> {
+    /// # New grammar
+    /// Create a ``Grammar`` without any rules
+    pub fn new() -> Grammar<'a, T> {
+        Grammar { rules: vec![] }
+    }
+
+    /// # With rule
+    /// Add a rule, consisting of a series of patterns and the handler to invoke
+    /// when the patterns are matched. For example:
+    ///
+    /// ``Token(Keyword("let")) AnyIdentifier Token(Operator("=")) Expression``
+    ///
+    /// A rule can span several fragments, when encapsulations are part of it,
+    /// as in ``Token(Keyword("if")) Expression Block(None)``.
+    pub fn with_rule<F>(mut self, patterns: Vec<Pattern>, handler: F) -> Grammar<'a, T>
+    where
+        F: Fn(&Grammar<'a, T>, &RuleMatch<'_>) -> T + 'a,
+    {
+        self.rules.insert(self.rules.len(), Rule {
+            patterns,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// # Run
+    /// Walk through the composition, and invoke the handler of the first
+    /// matching rule for each statement, single token and encapsulation.
+    /// The values returned by the handlers are returned in order.
+    ///
+    /// If no rule matches a fragment, a ``GrammarError`` is returned.
+    pub fn run<'c>(&self, composition: &'c Composition<'c>) -> Result<Vec<T>, GrammarError> {
+        let mut output: Vec<T> = vec![];
+        let mut index: usize = 0;
+
+        while index < composition.len() {
+            let (rule, matched, consumed) = self.rules
+                .iter()
+                .find_map(|rule| {
+                    Self::match_rule(&rule.patterns, &composition[index..])
+                        .map(|(matched, consumed)| (rule, matched, consumed))
+                })
+                .ok_or(NoRuleMatched { span: composition[index].get_span() })?;
+
+            output.insert(output.len(), (rule.handler)(self, &matched));
+            index += consumed;
+        }
+
+        Ok(output)
+    }
+
+    /// # Match rule
+    /// Attempt to match the patterns against the beginning of ``fragments``.
+    /// Returns the ``RuleMatch`` and the number of fragments it covers.
+    fn match_rule<'c>(
+        patterns: &[Pattern],
+        fragments: &'c [Fragment<'c>],
+    ) -> Option<(RuleMatch<'c>, usize)> {
+        let (captures, index) = Self::match_patterns(patterns, fragments, 0, 0)?;
+
+        if index == 0 {
+            return None;
+        }
+
+        let span: Span = fragments[0].get_span().join(&fragments[index - 1].get_span());
+        Some((RuleMatch { captures, span }, index))
+    }
+
+    /// # Match patterns
+    /// Match the patterns from the fragment at ``index``, where the first ``position``
+    /// tokens are already matched. Returns the captures and the index of the fragment
+    /// following the match.
+    fn match_patterns<'c>(
+        patterns: &[Pattern],
+        fragments: &'c [Fragment<'c>],
+        mut index: usize,
+        mut position: usize,
+    ) -> Option<(Vec<Capture<'c>>, usize)> {
+        let mut captures: Vec<Capture> = vec![];
+
+        for (number, pattern) in patterns.iter().enumerate() {
+            if let Block(bracket_type) = pattern {
+                // A statement in front of the block must be fully matched
+                if position > 0 {
+                    if !Self::is_fully_matched(Self::tokens_of(&fragments[index])?, position) {
+                        return None;
+                    }
+                    index += 1;
+                    position = 0;
+                }
+
+                match fragments.get(index) {
+                    Some(Fragment::Encapsulation(encapsulation))
+                        if bracket_type.is_none() || bracket_type.clone().map(Closure).as_ref() == Some(&encapsulation.context) => {
+                        captures.insert(captures.len(), Capture::Block(encapsulation));
+                        index += 1;
+                    },
+                    _ => return None,
+                }
+                continue;
+            }
+
+            if *pattern == Expression {
+                // The expression continues through the fragments, until the statement
+                // is terminated, or a block in curly brackets (which is never part of
+                // an expression) is found. The longest expression allowing the remaining
+                // patterns to match is used.
+                let (tokens, first, mut terminated): (&[Token], usize, bool) = match fragments.get(index) {
+                    Some(fragment) => match Self::tokens_of(fragment) {
+                        Some(tokens) => (&tokens[position..], index + 1, fragment.is_terminated()),
+                        None => (&[], index, false),
+                    },
+                    None => return None,
+                };
+
+                let mut last: usize = first;
+                while !terminated && last < fragments.len() {
+                    if fragments[last].get_context() == Some(Closure(BracketType::Curly)) {
+                        break;
+                    }
+                    terminated = fragments[last].is_terminated();
+                    last += 1;
+                }
+
+                for end in (first..=last).rev() {
+                    if tokens.is_empty() && end == first {
+                        break;
+                    }
+                    if let Some((rest, index)) = Self::match_patterns(&patterns[number + 1..], fragments, end, 0) {
+                        captures.insert(captures.len(), Capture::Expression(tokens, &fragments[first..end]));
+                        captures.extend(rest);
+                        return Some((captures, index));
+                    }
+                }
+                return None;
+            }
+
+            let tokens: &[Token] = Self::tokens_of(fragments.get(index)?)?;
+
+            // Doc comments in the middle of a statement document nothing, and are skipped
+            while tokens.get(position).is_some_and(Self::is_doc_comment) {
+                position += 1;
+            }
+            let token: &Token = tokens.get(position)?;
+
+            let is_match: bool = match (pattern, &token.token_type) {
+                (Token(expected), actual) => expected == actual,
+                (AnyKeyword, TokenType::Keyword(_))
+                | (AnyIdentifier, TokenType::Identifier(_))
+                | (AnyLiteral, TokenType::Literal(..))
+                | (AnyOperator, TokenType::Operator(_))
+                | (AnyPunctuator, TokenType::Punctuator(_))
+                | (AnyToken, _) => true,
+                _ => false,
+            };
+
+            if !is_match {
+                return None;
+            }
+
+            captures.insert(captures.len(), Capture::Token(token));
+            position += 1;
+        }
+
+        // The last statement must be fully matched as well
+        if position > 0 {
+            if !Self::is_fully_matched(Self::tokens_of(&fragments[index])?, position) {
+                return None;
+            }
+            index += 1;
+        }
+
+        Some((captures, index))
+    }
+
+    /// # Is fully matched
+    /// Returns true, if nothing but doc comments follow the first ``position`` tokens
+    fn is_fully_matched(tokens: &[Token], position: usize) -> bool {
+        tokens[position..].iter().all(Self::is_doc_comment)
+    }
+
+    /// # Is doc comment
+    /// Returns true for ``DocComment`` tokens
+    fn is_doc_comment(token: &Token) -> bool {
+        matches!(token.token_type, TokenType::DocComment(_))
+    }
+
+    /// # Tokens of
+    /// Returns the tokens of statements and single tokens,
+    /// while encapsulations return ``None``
+    fn tokens_of<'c>(fragment: &Fragment<'c>) -> Option<&'c [Token<'c>]> {
+        match fragment {
+            Fragment::Statement(statement) => Some(statement.tokens),
+            Fragment::SingleToken(single) => Some(std::slice::from_ref(single.token)),
+            Fragment::Encapsulation(_) => None,
+        }
+    }
+}
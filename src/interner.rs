@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+/// # Symbol
+/// A handle to a string stored in an ``Interner``.
+/// Two symbols from the same interner are equal when their strings are,
+/// which makes comparing them as cheap as comparing two integers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// # Index
+    /// Returns the position of the symbol in the interner.
+    /// Symbols are numbered from 0, in the order they were interned.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// # Interner
+/// Hands out a ``Symbol`` for every distinct string it is given.
+///
+/// An interner used for tokenizing should be created with
+/// ``LanguageDefinition::interner``, which makes the keywords its first symbols.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Interner {
+    symbols: HashMap<Box<str>, Symbol>,
+    strings: Vec<Box<str>>,
+}
+
+impl Interner {
+    /// # New interner
+    /// Create an empty ``Interner``
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// # Intern
+    /// Returns the symbol of ``text``, storing the text if it
+    /// hasn't been seen before
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(text) {
+            return *symbol;
+        }
+
+        let symbol: Symbol = Symbol(self.strings.len() as u32);
+        self.strings.insert(self.strings.len(), text.into());
+        self.symbols.insert(text.into(), symbol);
+        symbol
+    }
+
+    /// # Get
+    /// Returns the symbol of ``text``, if it has been interned
+    pub fn get(&self, text: &str) -> Option<Symbol> {
+        self.symbols.get(text).copied()
+    }
+
+    /// # Resolve
+    /// Returns the text of ``symbol``, if it belongs to this interner
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.index()).map(|text| &**text)
+    }
+
+    /// # Length
+    /// Returns the number of distinct strings in the interner
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// # Starts with
+    /// Returns true, if the first strings of the interner are those of ``other``,
+    /// in the same order, which gives them the same symbols in both
+    pub fn starts_with(&self, other: &Interner) -> bool {
+        self.strings.starts_with(&other.strings)
+    }
+
+    /// # Is empty
+    /// Returns true, if nothing has been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
@@ -1,14 +1,183 @@
+use crate::interner::{Interner, Symbol};
 use crate::tokenizer::TokenType::{self, *};
+use crate::langdef::{
+    Associativity::*,
+    EscapeSequence::*,
+    NumberFormat::*,
+};
+use unicode_xid::UnicodeXID;
 
 /// # Language Definition
 /// The struct describing the rules and looks of the language
 /// to be tokenized and interpreted
 #[derive(Debug, Clone, PartialEq)]
 pub struct LanguageDefinition {
-    keywords: Vec<&'static str>,
-    statement_terminator: TokenType,
-    block_opener: TokenType,
-    block_closer: TokenType,
+    // The keywords are interned first, so their symbols are the lowest
+    keywords: Interner,
+    operators: Vec<&'static str>,
+    punctuators: Vec<char>,
+    char_delimiter: Option<char>,
+    identifiers: IdentifierRules,
+    whitespace: WhitespaceRules,
+    line_comments: Vec<&'static str>,
+    block_comments: Vec<(&'static str, &'static str)>,
+    nested_comments: bool,
+    indentation: bool,
+    line_continuation: Option<char>,
+    statement_terminator: TokenType<'static>,
+    block_opener: TokenType<'static>,
+    block_closer: TokenType<'static>,
+    escapes: Vec<EscapeSequence>,
+    number_formats: Vec<NumberFormat>,
+    prefix_operators: Vec<(&'static str, u8)>,
+    infix_operators: Vec<(&'static str, u8, Associativity)>,
+    postfix_operators: Vec<(&'static str, u8)>,
+    modes: Vec<LexerMode>,
+}
+
+/// # Escape sequence
+/// Describes an escape sequence which can be used inside quoted strings.
+/// All escape sequences begin with a backslash (``\``).
+#[derive(Debug, Clone, PartialEq)]
+pub enum EscapeSequence {
+    /// A backslash followed by the first character is replaced by the second,
+    /// for example ``Char('n', '\n')``
+    Char(char, char),
+
+    /// A Unicode code point written as ``\u{XXXX}`` (one to six hexadecimal digits)
+    Unicode,
+
+    /// A character written as ``\xNN`` (exactly two hexadecimal digits)
+    Hex,
+}
+
+/// # Number format
+/// The notations which can be used for numbers, besides plain decimal
+/// integers (``30``) and decimals (``1.5``)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumberFormat {
+    /// Hexadecimal integers, such as ``0xFF``
+    Hexadecimal,
+
+    /// Binary integers, such as ``0b1010``
+    Binary,
+
+    /// Octal integers, such as ``0o17``
+    Octal,
+
+    /// Exponents, such as ``1e-9`` and ``2.5E3``
+    Exponent,
+
+    /// Underscores separating digits, such as ``1_000_000``
+    Separator,
+
+    /// Decimals without an integer part, such as ``.5``
+    LeadingDot,
+}
+
+/// # Associativity
+/// Describes how a series of infix operators with the same binding power
+/// are grouped. ``a - b - c`` is ``(a - b) - c`` with ``Left``, while
+/// ``a ** b ** c`` is ``a ** (b ** c)`` with ``Right``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+/// # Identifier rules
+/// Describes which characters identifiers (and keywords) are made of.
+/// Characters which can't be part of an identifier, and aren't
+/// operators, punctuators or whitespace either, are invalid.
+#[derive(Debug, Clone, Copy)]
+pub enum IdentifierRules {
+    /// Identifiers begin with a Unicode ``XID_Start`` character or ``_``,
+    /// followed by ``XID_Continue`` characters, like in Rust and Python
+    Unicode,
+
+    /// Identifiers begin with a character accepted by ``start``,
+    /// followed by characters accepted by ``continues``
+    Custom { start: fn(char) -> bool, continues: fn(char) -> bool },
+}
+
+impl PartialEq for IdentifierRules {
+    fn eq(&self, other: &IdentifierRules) -> bool {
+        match (self, other) {
+            (IdentifierRules::Unicode, IdentifierRules::Unicode) => true,
+            (
+                IdentifierRules::Custom { start, continues },
+                IdentifierRules::Custom { start: other_start, continues: other_continues },
+            ) => std::ptr::fn_addr_eq(*start, *other_start) && std::ptr::fn_addr_eq(*continues, *other_continues),
+            _ => false,
+        }
+    }
+}
+
+/// # Whitespace rules
+/// Describes which characters separate tokens, besides line feeds
+/// and carriage returns, which always do
+#[derive(Debug, Clone, Copy)]
+pub enum WhitespaceRules {
+    /// All Unicode whitespace (``char::is_whitespace``), such as spaces,
+    /// tabs and non-breaking spaces
+    Unicode,
+
+    /// The characters accepted by the function
+    Custom(fn(char) -> bool),
+}
+
+impl PartialEq for WhitespaceRules {
+    fn eq(&self, other: &WhitespaceRules) -> bool {
+        match (self, other) {
+            (WhitespaceRules::Unicode, WhitespaceRules::Unicode) => true,
+            (WhitespaceRules::Custom(rule), WhitespaceRules::Custom(other_rule)) => std::ptr::fn_addr_eq(*rule, *other_rule),
+            _ => false,
+        }
+    }
+}
+
+/// # Lexer mode
+/// A different rule set, which the lexer switches to after the ``opener`` token
+/// (found by the rules it came from), like the modes of ANTLR. At the end of the
+/// mode, the lexer returns to the rules it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexerMode {
+    /// The tokens are found by another ``language``, such as SQL inside ``sql { ... }``,
+    /// until its ``closer`` token appears outside the brackets opened in the mode.
+    /// The closer is part of the mode, and the modes of the language can be entered in turn
+    Language {
+        name: &'static str,
+        opener: TokenType<'static>,
+        language: Box<LanguageDefinition>,
+        closer: TokenType<'static>,
+    },
+
+    /// The text up to the ``closer`` is kept as a single ``Embedded`` token,
+    /// such as a regular expression. A closer preceded by a backslash
+    /// is part of the text
+    Raw {
+        name: &'static str,
+        opener: TokenType<'static>,
+        closer: &'static str,
+    },
+}
+
+impl LexerMode {
+    /// # Name
+    /// Returns the name of the mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            LexerMode::Language { name, .. } | LexerMode::Raw { name, .. } => name,
+        }
+    }
+
+    /// # Opener
+    /// Returns the token which enters the mode
+    pub fn opener(&self) -> &TokenType<'static> {
+        match self {
+            LexerMode::Language { opener, .. } | LexerMode::Raw { opener, .. } => opener,
+        }
+    }
 }
 
 impl LanguageDefinition {
@@ -17,18 +186,383 @@ impl LanguageDefinition {
     pub fn new(
         keywords: Vec<&'static str>,
     ) -> LanguageDefinition {
+        let mut interner: Interner = Interner::new();
+        keywords.iter().for_each(|keyword| {
+            interner.intern(keyword);
+        });
+
         LanguageDefinition {
-            keywords,
+            keywords: interner,
+            operators: vec![
+                "+", "-", "*", "/", "%", "**",
+                "=", "+=", "-=", "*=", "/=", "%=",
+                "==", "!=", "<", ">", "<=", ">=",
+                "!", "&&", "||",
+                "->", "=>", "::",
+            ],
+            punctuators: vec![';', '{', '}', '(', ')', '[', ']', ',', '.', ':', '?'],
+            char_delimiter: Some('\''),
+            identifiers: IdentifierRules::Unicode,
+            whitespace: WhitespaceRules::Unicode,
+            line_comments: vec!["//", "#"],
+            block_comments: vec![("/*", "*/")],
+            nested_comments: false,
+            indentation: false,
+            line_continuation: None,
             statement_terminator: Punctuator(';'),
             block_opener: Punctuator('{'),
             block_closer: Punctuator('}'),
+            escapes: vec![
+                Char('"', '"'),
+                Char('\'', '\''),
+                Char('\\', '\\'),
+                Char('n', '\n'),
+                Char('t', '\t'),
+                Char('r', '\r'),
+                Char('0', '\0'),
+                Unicode,
+                Hex,
+            ],
+            number_formats: vec![Hexadecimal, Binary, Octal, Exponent, Separator, LeadingDot],
+            prefix_operators: vec![("-", 8), ("!", 8)],
+            infix_operators: vec![
+                ("=", 1, Right), ("+=", 1, Right), ("-=", 1, Right),
+                ("*=", 1, Right), ("/=", 1, Right), ("%=", 1, Right),
+                ("||", 2, Left),
+                ("&&", 3, Left),
+                ("==", 4, Left), ("!=", 4, Left),
+                ("<", 5, Left), (">", 5, Left), ("<=", 5, Left), (">=", 5, Left),
+                ("+", 6, Left), ("-", 6, Left),
+                ("*", 7, Left), ("/", 7, Left), ("%", 7, Left),
+                ("**", 9, Right),
+            ],
+            postfix_operators: vec![],
+            modes: vec![],
         }
     }
 
+    /// # With operators
+    /// Replace the operators recognized by the language.
+    /// Operators are matched by longest match, meaning ``<=`` is preferred
+    /// over ``<`` when both are defined.
+    pub fn with_operators(mut self, operators: Vec<&'static str>) -> LanguageDefinition {
+        self.operators = operators;
+        self
+    }
+
+    /// # With punctuators
+    /// Replace the characters which are recognized as punctuators,
+    /// such as brackets, commas and statement terminators
+    pub fn with_punctuators(mut self, punctuators: Vec<char>) -> LanguageDefinition {
+        self.punctuators = punctuators;
+        self
+    }
+
+    /// # With statement terminator
+    /// Set the token which ends a statement, such as ``Punctuator(';')``
+    /// (the default), ``Punctuator('.')`` or ``Newline``.
+    ///
+    /// With ``Newline``, a line break doesn't end the statement inside parentheses
+    /// and square brackets, after a binary operator, or after the line continuation.
+    pub fn with_statement_terminator(mut self, terminator: TokenType<'static>) -> LanguageDefinition {
+        self.statement_terminator = terminator;
+        self
+    }
+
+    /// # With block delimiters
+    /// Set the tokens which open and close a block. By default these are
+    /// ``Punctuator('{')`` and ``Punctuator('}')``, but keywords such as
+    /// ``begin`` and ``end`` can be used as well
+    pub fn with_block_delimiters(mut self, opener: TokenType<'static>, closer: TokenType<'static>) -> LanguageDefinition {
+        self.block_opener = opener;
+        self.block_closer = closer;
+        self
+    }
+
+    /// # With char delimiter
+    /// Set the character surrounding character literals, such as ``'a'``.
+    /// With ``None``, the language has no character literals.
+    pub fn with_char_delimiter(mut self, delimiter: Option<char>) -> LanguageDefinition {
+        self.char_delimiter = delimiter;
+        self
+    }
+
+    /// # With line continuation
+    /// Set the character which continues a statement on the next line,
+    /// such as ``\``, when newlines are significant. Only whitespace
+    /// may follow it on the line.
+    /// With ``None`` (the default), the language has no line continuation.
+    pub fn with_line_continuation(mut self, continuation: Option<char>) -> LanguageDefinition {
+        self.line_continuation = continuation;
+        self
+    }
+
+    /// # With line comments
+    /// Replace the prefixes which begin a comment running to the end of
+    /// the line. By default these are ``//`` and ``#``.
+    pub fn with_line_comments(mut self, prefixes: Vec<&'static str>) -> LanguageDefinition {
+        self.line_comments = prefixes;
+        self
+    }
+
+    /// # With block comments
+    /// Replace the pairs of delimiters surrounding block comments, such as
+    /// ``("/*", "*/")`` (the default) or ``("{-", "-}")``.
+    /// Block comments where the opener is followed by its last character
+    /// once more, such as ``/**``, are doc comments.
+    pub fn with_block_comments(mut self, delimiters: Vec<(&'static str, &'static str)>) -> LanguageDefinition {
+        self.block_comments = delimiters;
+        self
+    }
+
+    /// # With nested comments
+    /// Allow block comments inside block comments, in which case a block
+    /// comment only ends when every comment opened inside it is closed
+    pub fn with_nested_comments(mut self, nested: bool) -> LanguageDefinition {
+        self.nested_comments = nested;
+        self
+    }
+
+    /// # With identifiers
+    /// Set the characters identifiers are made of. By default these are
+    /// the Unicode identifier characters (``IdentifierRules::Unicode``).
+    pub fn with_identifiers(mut self, rules: IdentifierRules) -> LanguageDefinition {
+        self.identifiers = rules;
+        self
+    }
+
+    /// # With whitespace
+    /// Set the characters which separate tokens. By default this is
+    /// any Unicode whitespace (``WhitespaceRules::Unicode``).
+    pub fn with_whitespace(mut self, rules: WhitespaceRules) -> LanguageDefinition {
+        self.whitespace = rules;
+        self
+    }
+
+    /// # With significant indentation
+    /// Make blocks depend on indentation, like in Python. The tokenizer then adds
+    /// a ``Newline`` token at every line break, and an ``Indent`` or ``Dedent`` token
+    /// whenever the indentation of a line is deeper or shallower than the line before.
+    /// Line breaks and indentation inside brackets are ignored.
+    pub fn with_significant_indentation(mut self, indentation: bool) -> LanguageDefinition {
+        self.indentation = indentation;
+        self
+    }
+
+    /// # With modes
+    /// Set the lexer modes, which switch to a different rule set
+    /// after their opener. By default the language has no modes.
+    pub fn with_modes(mut self, modes: Vec<LexerMode>) -> LanguageDefinition {
+        self.modes = modes;
+        self
+    }
+
+    /// # With escapes
+    /// Replace the escape sequences which are recognized inside quoted strings.
+    /// When no escape sequences are given, backslashes have no special meaning.
+    pub fn with_escapes(mut self, escapes: Vec<EscapeSequence>) -> LanguageDefinition {
+        self.escapes = escapes;
+        self
+    }
+
+    /// # With number formats
+    /// Replace the notations which can be used for numbers.
+    /// Plain decimal integers and decimals are always recognized.
+    pub fn with_number_formats(mut self, formats: Vec<NumberFormat>) -> LanguageDefinition {
+        self.number_formats = formats;
+        self
+    }
+
+    /// # With prefix operators
+    /// Replace the operators which can be placed in front of an operand,
+    /// along with their binding power (higher binds tighter)
+    pub fn with_prefix_operators(mut self, operators: Vec<(&'static str, u8)>) -> LanguageDefinition {
+        self.prefix_operators = operators;
+        self
+    }
+
+    /// # With infix operators
+    /// Replace the operators which can be placed between two operands,
+    /// along with their binding power (higher binds tighter) and associativity
+    pub fn with_infix_operators(mut self, operators: Vec<(&'static str, u8, Associativity)>) -> LanguageDefinition {
+        self.infix_operators = operators;
+        self
+    }
+
+    /// # With postfix operators
+    /// Replace the operators which can be placed after an operand,
+    /// along with their binding power (higher binds tighter)
+    pub fn with_postfix_operators(mut self, operators: Vec<(&'static str, u8)>) -> LanguageDefinition {
+        self.postfix_operators = operators;
+        self
+    }
+
+    /// # Has number format
+    /// Returns true, if the number notation is enabled in the language definition
+    pub fn has_number_format(&self, format: NumberFormat) -> bool {
+        self.number_formats.contains(&format)
+    }
+
+    /// # Prefix binding power
+    /// Returns the binding power of ``operator`` when used as a prefix operator
+    pub fn prefix_binding_power(&self, operator: &str) -> Option<u8> {
+        self.prefix_operators.iter().find(|(o, _)| *o == operator).map(|(_, power)| *power)
+    }
+
+    /// # Infix binding power
+    /// Returns the binding power and associativity of ``operator``
+    /// when used as an infix operator
+    pub fn infix_binding_power(&self, operator: &str) -> Option<(u8, Associativity)> {
+        self.infix_operators.iter().find(|(o, _, _)| *o == operator).map(|(_, power, associativity)| (*power, *associativity))
+    }
+
+    /// # Postfix binding power
+    /// Returns the binding power of ``operator`` when used as a postfix operator
+    pub fn postfix_binding_power(&self, operator: &str) -> Option<u8> {
+        self.postfix_operators.iter().find(|(o, _)| *o == operator).map(|(_, power)| *power)
+    }
+
+    /// # Char delimiter
+    /// Returns the character surrounding character literals
+    pub fn char_delimiter(&self) -> Option<char> {
+        self.char_delimiter
+    }
+
+    /// # Is identifier start
+    /// Returns true, if an identifier can begin with the ``character``
+    pub fn is_identifier_start(&self, character: char) -> bool {
+        match self.identifiers {
+            IdentifierRules::Unicode => character == '_' || character.is_xid_start(),
+            IdentifierRules::Custom { start, .. } => start(character),
+        }
+    }
+
+    /// # Is identifier continue
+    /// Returns true, if the ``character`` can be part of an identifier,
+    /// after the first character
+    pub fn is_identifier_continue(&self, character: char) -> bool {
+        match self.identifiers {
+            IdentifierRules::Unicode => character.is_xid_continue(),
+            IdentifierRules::Custom { continues, .. } => continues(character),
+        }
+    }
+
+    /// # Is whitespace
+    /// Returns true, if the ``character`` separates tokens
+    pub fn is_whitespace(&self, character: char) -> bool {
+        match self.whitespace {
+            _ if character == '\n' || character == '\r' => true,
+            WhitespaceRules::Unicode => character.is_whitespace(),
+            WhitespaceRules::Custom(rule) => rule(character),
+        }
+    }
+
+    /// # Line comments
+    /// Returns the prefixes which begin a comment running to the end of the line
+    pub fn line_comments(&self) -> &[&'static str] {
+        &self.line_comments
+    }
+
+    /// # Block comments
+    /// Returns the pairs of delimiters surrounding block comments
+    pub fn block_comments(&self) -> &[(&'static str, &'static str)] {
+        &self.block_comments
+    }
+
+    /// # Has nested comments
+    /// Returns true, if block comments can be nested
+    pub fn has_nested_comments(&self) -> bool {
+        self.nested_comments
+    }
+
+    /// # Statement terminator
+    /// Returns the token which ends a statement
+    pub fn statement_terminator(&self) -> &TokenType<'static> {
+        &self.statement_terminator
+    }
+
+    /// # Block opener
+    /// Returns the token which opens a block
+    pub fn block_opener(&self) -> &TokenType<'static> {
+        &self.block_opener
+    }
+
+    /// # Block closer
+    /// Returns the token which closes a block
+    pub fn block_closer(&self) -> &TokenType<'static> {
+        &self.block_closer
+    }
+
+    /// # Has significant newlines
+    /// Returns true, if newlines have a meaning in the language,
+    /// and must therefore be kept in the token stream
+    pub fn has_significant_newlines(&self) -> bool {
+        self.statement_terminator == Newline || self.indentation
+    }
+
+    /// # Has significant indentation
+    /// Returns true, if blocks depend on indentation
+    pub fn has_significant_indentation(&self) -> bool {
+        self.indentation
+    }
+
+    /// # Modes
+    /// Returns the lexer modes of the language
+    pub fn modes(&self) -> &[LexerMode] {
+        &self.modes
+    }
+
+    /// # Line continuation
+    /// Returns the character which continues a statement on the next line, if any
+    pub fn line_continuation(&self) -> Option<char> {
+        self.line_continuation
+    }
+
+    /// # Operators
+    /// Returns the operators recognized by the language
+    pub fn operators(&self) -> &[&'static str] {
+        &self.operators
+    }
+
+    /// # Escapes
+    /// Returns the escape sequences recognized inside quoted strings
+    pub fn escapes(&self) -> &[EscapeSequence] {
+        &self.escapes
+    }
+
     /// # Has keyword
     /// Returns true, if the ``keyword`` parameter is defined
     /// as a keyword in the language definition
-    pub fn has_keyword(&self, keyword: &String) -> bool {
-        self.keywords.contains(&&**keyword)
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords.get(keyword).is_some()
+    }
+
+    /// # Is keyword
+    /// Returns true, if the ``symbol`` is one of the keywords. The symbol must
+    /// come from an interner created with ``LanguageDefinition::interner``,
+    /// in which case this is a single comparison.
+    pub fn is_keyword(&self, symbol: Symbol) -> bool {
+        symbol.index() < self.keywords.len()
+    }
+
+    /// # Has keyword symbols
+    /// Returns true, if the keywords are the first symbols of the ``interner``,
+    /// as in interners created with ``LanguageDefinition::interner``
+    pub fn has_keyword_symbols(&self, interner: &Interner) -> bool {
+        interner.starts_with(&self.keywords)
+    }
+
+    /// # Interner
+    /// Create an ``Interner`` in which the keywords are the first symbols,
+    /// so tokens interned with it can be recognized with ``is_keyword``
+    pub fn interner(&self) -> Interner {
+        self.keywords.clone()
+    }
+
+    /// # Has punctuator
+    /// Returns true, if the ``character`` is defined as a punctuator
+    /// in the language definition
+    pub fn has_punctuator(&self, character: char) -> bool {
+        self.punctuators.contains(&character)
     }
 }
@@ -0,0 +1,1330 @@
+use crate::interner::{Interner, Symbol};
+use crate::langdef::{
+    EscapeSequence,
+    LanguageDefinition,
+    LexerMode,
+    NumberFormat,
+};
+use crate::lexer::Context::*;
+use crate::span::{Position, Span};
+use crate::tokenizer::{
+    LiteralKind,
+    Token,
+    TokenizeError::{self, *},
+    TokenStream,
+    TokenType::{self, *},
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{ErrorKind, Read},
+    str::Chars,
+};
+
+/// The number of bytes read from a reader at a time
+const CHUNK_SIZE: usize = 8192;
+
+/// # Operator table
+/// The operators of the language, grouped by their first character and
+/// sorted longest first. This way only the operators which can possibly
+/// match are compared, and the first match is the longest.
+/// Operators beginning with an ASCII character are found without hashing.
+struct OperatorTable {
+    ascii: Vec<Vec<&'static str>>,
+    other: HashMap<char, Vec<&'static str>>,
+}
+
+impl OperatorTable {
+    fn new(langdef: &LanguageDefinition) -> OperatorTable {
+        let mut table: OperatorTable = OperatorTable {
+            ascii: vec![vec![]; 128],
+            other: HashMap::new(),
+        };
+
+        for operator in langdef.operators() {
+            match operator.chars().next() {
+                Some(first) if first.is_ascii() => table.ascii[first as usize].push(operator),
+                Some(first) => table.other.entry(first).or_default().push(operator),
+                None => {},
+            }
+        }
+
+        for candidates in table.ascii.iter_mut().chain(table.other.values_mut()) {
+            candidates.sort_by_key(|operator| std::cmp::Reverse(operator.len()));
+            candidates.dedup();
+        }
+
+        table
+    }
+
+    /// # Find
+    /// Returns the longest operator found at the very beginning of ``text``
+    fn find(&self, text: &str) -> Option<&'static str> {
+        let first: char = text.chars().next()?;
+        let candidates: &Vec<&'static str> = match first.is_ascii() {
+            true => &self.ascii[first as usize],
+            false => self.other.get(&first)?,
+        };
+        candidates.iter().find(|operator| text.starts_with(*operator)).copied()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Context {
+    /// Inside a string or character literal, ended by the ``char``
+    Quotes(char),
+    Comment,
+
+    /// Inside a block comment, which is ended by the ``closer``. When nesting is
+    /// allowed, ``nested`` holds the positions of the comments opened inside it,
+    /// which are still open. Doc comments (``documentation``) are kept as a ``DocComment``
+    DocBlock { opener: &'static str, closer: &'static str, nested: Vec<Position>, documentation: bool },
+
+    /// Inside a run of spaces, which is only tracked when trivia is kept
+    Blank,
+
+    /// Inside a quoted string, right after a backslash. The ``sequence``
+    /// holds the characters following the backslash (found at ``start``)
+    Escape { delimiter: char, sequence: String, start: Position },
+
+    /// Inside a raw lexer mode (``name``), which is ended by the ``closer``
+    Raw { name: &'static str, closer: &'static str },
+}
+
+/// # Active mode
+/// A lexer mode which has been entered, along with the rules to return to
+struct ActiveMode<'l> {
+    mode: &'l LexerMode,
+    outer: &'l LanguageDefinition,
+
+    // The number of brackets open when the mode was entered, and where it was entered
+    brackets: usize,
+    start: Position,
+}
+
+/// # Line indentation
+/// The leading whitespace of the current line, measured up to its first token
+#[derive(Debug, Default)]
+struct LineIndentation {
+    width: usize,
+
+    // The character used for the indentation, and the position of the
+    // first character which differs from it (or from earlier lines)
+    character: Option<char>,
+    mixed: Option<Position>,
+
+    // Whether a comment was found in front of the first token,
+    // after which the whitespace no longer counts
+    commented: bool,
+}
+
+/// # Lexer
+/// Produces tokens on demand, as an ``Iterator`` of ``Result<Token, TokenizeError>``.
+/// Tokens from a ``&str`` borrow their text from it, whenever possible.
+///
+/// The document is either a ``&str`` (``Lexer::new``), or any ``Read`` source,
+/// such as a ``File`` or a ``BufReader`` (``Lexer::from_reader``), which is read
+/// in chunks, so the document never has to be fully loaded.
+///
+/// The iterator ends after the first error.
+pub struct Lexer<'l, 'src> {
+    langdef: &'l LanguageDefinition,
+    operators: OperatorTable,
+
+    // The text currently available. For a ``&str`` this is the entire document,
+    // while for readers it's the most recently read chunk(s)
+    input: Cow<'src, str>,
+
+    // The entire document, when it's available to borrow from
+    source: Option<&'src str>,
+    cursor: usize,
+
+    // The reader, if any, along with bytes of a character split between two chunks,
+    // and the position of invalid UTF-8 found by the reader
+    reader: Option<Box<dyn Read + 'src>>,
+    incomplete: Vec<u8>,
+    invalid: Option<Position>,
+
+    // The number of bytes which must be available ahead of the cursor,
+    // to recognize the longest operator
+    lookahead: usize,
+
+    // This variable is to remember if we have entered a certain context,
+    // for example inside quoted strings or comments, which require different
+    // handling than other scenarios
+    context: Option<Context>,
+
+    // The buffer holds none, one or several characters, which are picked up,
+    // until we figure out what to do with them
+    buffer: String,
+
+    // The position of the character currently being looked at, and the
+    // position at which the content of the buffer begins
+    position: Position,
+    start: Position,
+
+    // The previous character in the document
+    prev: Option<char>,
+
+    // Tokens which are found, but not yet handed out
+    pending: TokenStream<'src>,
+    finished: bool,
+
+    // Stores the text of keywords and identifiers, when interning is enabled,
+    // and whether the keywords are its first symbols
+    interner: Option<Interner>,
+    keyword_symbols: bool,
+
+    // Whether whitespace and comments are added to the stream
+    trivia: bool,
+
+    // With significant indentation: the widths of the enclosing indentation levels,
+    // the indentation of the current line (until its first token is found), and
+    // the character used for indentation in the document
+    indents: Vec<usize>,
+    indentation: Option<LineIndentation>,
+    indent_char: Option<char>,
+
+    // The brackets which are currently open, innermost last
+    brackets: Vec<char>,
+
+    // Whether the statement on the current line continues on the next,
+    // because the line ends with a binary operator or the line continuation.
+    // The line continuation found on the current line, and where
+    line_continues: bool,
+    continuation: Option<(char, Position)>,
+
+    // The lexer modes which have been entered, innermost last.
+    // While in a mode, ``langdef`` holds the rules of the mode
+    modes: Vec<ActiveMode<'l>>,
+}
+
+impl<'l, 'src> Lexer<'l, 'src> {
+    /// # New lexer
+    /// Create a ``Lexer`` which tokenizes the ``document``
+    pub fn new(langdef: &'l LanguageDefinition, document: &'src str) -> Lexer<'l, 'src> {
+        Lexer::create(langdef, Cow::Borrowed(document), Some(document), None)
+    }
+
+    /// # From reader
+    /// Create a ``Lexer`` which reads the document from ``reader``, a chunk at a time.
+    /// The document must be valid UTF-8.
+    pub fn from_reader<R: Read + 'src>(langdef: &'l LanguageDefinition, reader: R) -> Lexer<'l, 'src> {
+        Lexer::create(langdef, Cow::Owned(String::new()), None, Some(Box::new(reader)))
+    }
+
+    fn create(
+        langdef: &'l LanguageDefinition,
+        input: Cow<'src, str>,
+        source: Option<&'src str>,
+        reader: Option<Box<dyn Read + 'src>>,
+    ) -> Lexer<'l, 'src> {
+        Lexer {
+            langdef,
+            operators: OperatorTable::new(langdef),
+            input,
+            source,
+            cursor: 0,
+            reader,
+            incomplete: vec![],
+            invalid: None,
+            lookahead: Self::lookahead(langdef).max(2) + 8,
+            context: None,
+            buffer: String::new(),
+            position: Position::start(),
+            start: Position::start(),
+            prev: None,
+            pending: TokenStream::new(),
+            finished: false,
+            interner: None,
+            keyword_symbols: false,
+            trivia: false,
+            indents: vec![],
+            indentation: Some(LineIndentation::default()),
+            indent_char: None,
+            brackets: vec![],
+            line_continues: false,
+            continuation: None,
+            modes: vec![],
+        }
+    }
+
+    /// # Lookahead
+    /// Returns the length of the longest text recognized at once, among
+    /// the language and its lexer modes
+    fn lookahead(langdef: &LanguageDefinition) -> usize {
+        // Besides the operator, up to two characters are looked at (four bytes each).
+        // Block comments are recognized by their opener, followed by a character
+        // (which makes it a doc comment) or the closer
+        let longest_operator: usize = langdef.operators()
+            .iter()
+            .chain(langdef.line_comments())
+            .map(|operator| operator.len())
+            .chain(langdef.block_comments().iter().map(|(opener, closer)| opener.len() + closer.len()))
+            .max()
+            .unwrap_or(0);
+
+        langdef.modes()
+            .iter()
+            .map(|mode| match mode {
+                LexerMode::Language { language, .. } => Self::lookahead(language),
+                LexerMode::Raw { closer, .. } => closer.len(),
+            })
+            .fold(longest_operator, usize::max)
+    }
+
+    /// # With trivia
+    /// Keep whitespace, newlines, comments and docblocks as tokens, so the
+    /// document can be reproduced from the stream
+    pub fn with_trivia(mut self) -> Lexer<'l, 'src> {
+        self.trivia = true;
+        self
+    }
+
+    /// # With interner
+    /// Intern the text of keywords and identifiers, which gives their
+    /// tokens a ``Symbol``. Keywords are then recognized by their symbol,
+    /// when the interner is created with ``LanguageDefinition::interner``.
+    /// An empty interner is replaced by one that is. With other interners,
+    /// and inside lexer modes, keywords are recognized by their text.
+    pub fn with_interner(mut self, interner: Interner) -> Lexer<'l, 'src> {
+        let interner: Interner = match interner.is_empty() {
+            true => self.langdef.interner(),
+            false => interner,
+        };
+        self.keyword_symbols = self.langdef.has_keyword_symbols(&interner);
+        self.interner = Some(interner);
+        self
+    }
+
+    /// # Interner
+    /// Returns the interner, if interning is enabled
+    pub fn interner(&self) -> Option<&Interner> {
+        self.interner.as_ref()
+    }
+
+    /// # Into interner
+    /// Consumes the lexer, and returns the interner (if interning is enabled)
+    /// with the text of every keyword and identifier found so far
+    pub fn into_interner(self) -> Option<Interner> {
+        self.interner
+    }
+
+    /// # Refill
+    /// Read from the reader, until the lookahead is available
+    /// or the reader is exhausted
+    fn refill(&mut self) -> Result<(), TokenizeError> {
+        while self.input.len() - self.cursor < self.lookahead {
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None => return Ok(()),
+            };
+
+            let mut chunk: [u8; CHUNK_SIZE] = [0; CHUNK_SIZE];
+            let read: usize = match reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => return Err(Io { kind: error.kind(), message: error.to_string() }),
+            };
+
+            // The reader is exhausted. A character left incomplete is invalid
+            if read == 0 {
+                self.reader = None;
+                if !self.incomplete.is_empty() {
+                    self.invalid = Some(self.end_of_input());
+                }
+                return Ok(());
+            }
+
+            // Characters which are already consumed are discarded
+            let input: &mut String = self.input.to_mut();
+            input.drain(..self.cursor);
+            self.cursor = 0;
+
+            // A character can be split between two chunks, in which case
+            // the beginning of it is kept until the next chunk is read
+            self.incomplete.extend_from_slice(&chunk[..read]);
+            // Invalid UTF-8 ends the input, and the text in front of it is still tokenized
+            let (valid, invalid): (usize, bool) = match std::str::from_utf8(&self.incomplete) {
+                Ok(text) => (text.len(), false),
+                Err(error) => (error.valid_up_to(), error.error_len().is_some()),
+            };
+            input.push_str(std::str::from_utf8(&self.incomplete[..valid]).unwrap_or_default());
+            self.incomplete.drain(..valid);
+
+            if invalid {
+                self.reader = None;
+                self.invalid = Some(self.end_of_input());
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # End of input
+    /// Returns the position following the input read so far
+    fn end_of_input(&self) -> Position {
+        let mut position: Position = self.position;
+        self.input[self.cursor..].chars().for_each(|c| position.advance(c));
+        position
+    }
+
+    fn invalid_utf8(position: Position) -> TokenizeError {
+        Io {
+            kind: ErrorKind::InvalidData,
+            message: format!("Stream did not contain valid UTF-8 (at {})", position),
+        }
+    }
+
+    /// # Step
+    /// Look at the next character of the document, which adds
+    /// none, one or several tokens to ``pending``
+    fn step(&mut self) -> Result<(), TokenizeError> {
+        self.refill()?;
+
+        let mut chars: Chars = self.input[self.cursor..].chars();
+        let e: char = match (chars.next(), self.invalid) {
+            (Some(e), _) => e,
+            (None, None) => return self.finish(),
+
+            // Invalid UTF-8 is reported once the tokens in front of it are handed out
+            (None, Some(position)) => {
+                self.flush(self.position)?;
+                return match self.pending.is_empty() {
+                    true => Err(Self::invalid_utf8(position)),
+                    false => Ok(()),
+                };
+            },
+        };
+
+        // Shorthand to determine if we have encountered the end of the line
+        let is_eol: bool = e == '\n' || e == '\r';
+
+        // The number of characters consumed
+        let mut consumed: usize = 1;
+
+        // A run of spaces ends at the first other character, which
+        // is then handled like outside the run
+        if matches!(self.context, Some(Blank)) && !self.is_blank(e) {
+            self.add_trivia(Whitespace, self.position);
+            self.context = None;
+        }
+
+        // Outside any context, comments begin with one of the delimiters
+        // declared in the language definition
+        let rest: &str = &self.input[self.cursor..];
+        let (block_comment, line_comment) = match self.context {
+            None => (
+                self.langdef.block_comments().iter().find(|(opener, _)| !opener.is_empty() && rest.starts_with(opener)).copied(),
+                self.langdef.line_comments().iter().find(|prefix| !prefix.is_empty() && rest.starts_with(*prefix)).copied(),
+            ),
+            _ => (None, None),
+        };
+
+        // At the beginning of a line, the indentation is measured up to the first
+        // token, which is preceded by ``Indent`` or ``Dedent`` tokens when the indentation
+        // changes. Blank lines and lines holding nothing but comments are left out
+        if self.langdef.has_significant_indentation() && matches!(self.context, None | Some(Blank)) {
+            let used: Option<char> = self.indent_char;
+            match &mut self.indentation {
+                Some(line) if block_comment.is_some() => line.commented = true,
+                Some(line) if line.commented && e != '\n' && self.langdef.is_whitespace(e) => {},
+                Some(line) if e != '\n' && self.langdef.is_whitespace(e) => {
+                    line.width += 1;
+                    if line.character.or(used).is_some_and(|c| c != e) && line.mixed.is_none() {
+                        line.mixed = Some(self.position);
+                    }
+                    line.character = line.character.or(Some(e));
+                },
+                Some(_) if is_eol || line_comment.is_some() => {},
+                Some(_) => {
+                    let line: LineIndentation = self.indentation.take().unwrap_or_default();
+                    self.indent(line)?;
+                },
+                None => {},
+            }
+        }
+
+        // When there's no context and we find the opener of a block comment
+        // we're entering a docblock. Docblocks where the opener is followed by its
+        // last character once more, such as /** (but not the empty /**/), document
+        // the code following them, and are kept
+        if let Some((opener, closer)) = block_comment {
+            let after: &str = &self.input[self.cursor + opener.len()..];
+            let documentation: bool = opener.chars().next_back().is_some_and(|last| after.starts_with(last))
+                && !after.starts_with(closer);
+
+            self.flush(self.position)?;
+            self.start = self.position;
+            self.context = Some(DocBlock { opener, closer, nested: vec![], documentation });
+            if documentation || self.trivia {
+                self.buffer.push_str(opener);
+            }
+            consumed = opener.chars().count();
+
+        // Inside a docblock, we look for its closer (and for nested docblocks).
+        // Since docblocks should be ignored, we will not do anything with
+        // eventual buffer content, unless it's a doc comment or trivia is kept
+        } else if matches!(self.context, Some(DocBlock { .. })) {
+            consumed = self.context_docblock();
+
+        // Inside a raw lexer mode, the text is collected until the closer
+        } else if matches!(self.context, Some(Raw { .. })) {
+            consumed = self.context_raw();
+
+        // If we aren't in an established context, but encounter the prefix of
+        // a line comment, we will enter a comment context (which is also just to be ignored)
+        } else if let Some(prefix) = line_comment {
+            self.flush(self.position)?;
+            self.start = self.position;
+            self.context = Some(Comment);
+            if self.trivia {
+                self.buffer.push_str(prefix);
+            }
+            consumed = prefix.chars().count();
+
+        // When we are inside a quote context, we want to add the character to the
+        // the buffer, unless it's a quote, in which case we leave quote context.
+        // Escape sequences are decoded along the way
+        } else if matches!(self.context, Some(Quotes(_)) | Some(Escape { .. })) {
+            self.context_quotes(e)?;
+
+        // Line comments are terminated when encountering the end of the line.
+        // The end of the line itself is handled like outside the comment, since it
+        // can be significant to the language
+        } else if matches!(self.context, Some(Comment)) && is_eol {
+            if self.trivia {
+                self.add_trivia(LineComment, self.position);
+            }
+            self.context = None;
+            self.context_none()?;
+
+        // We ignore the contents of comments, and the
+        // spaces between tokens, unless trivia is kept
+        } else if matches!(self.context, Some(Comment) | Some(Blank)) {
+            self.keep_trivia(e);
+
+        // When there's no defined context, we will use a match pattern to decide what
+        // should happen, based on which character we've seen
+        } else if self.context.is_none() {
+            consumed = self.context_none()?;
+        }
+
+        // The last token found decides whether the line continues.
+        // The line continuation comes after any token ending in front of it
+        if let Some(token) = self.pending.iter().rev().find(|token| Self::is_code(token)) {
+            self.line_continues = Self::continues_line(self.langdef, &token.token_type);
+        }
+        if self.continuation.is_some() {
+            self.line_continues = true;
+        }
+
+        // The tokens found can enter or leave a lexer mode
+        self.switch_modes();
+
+        // Characters consumed as part of a token (such as an operator
+        // spanning multiple characters) are skipped
+        let rest: &str = &self.input[self.cursor..];
+        for c in rest.chars().take(consumed) {
+            self.prev = Some(c);
+            self.position.advance(c);
+            self.cursor += c.len_utf8();
+        }
+
+        Ok(())
+    }
+
+    /// # Finish
+    /// Called when the end of the document is reached
+    fn finish(&mut self) -> Result<(), TokenizeError> {
+        self.finished = true;
+
+        // The line continuation must be followed by a line break
+        if let Some((character, position)) = self.continuation {
+            return Err(InvalidCharacter { character, position });
+        }
+
+        // Strings and docblocks must be closed before the document ends.
+        // Of nested docblocks, the innermost one still open is reported
+        match &self.context {
+            Some(Quotes(_)) | Some(Escape { .. }) => return Err(UnterminatedString { start: self.start }),
+            Some(DocBlock { nested, .. }) => return Err(UnterminatedDocBlock {
+                start: nested.last().copied().unwrap_or(self.start),
+            }),
+            Some(Raw { name, .. }) => return Err(UnterminatedMode { name, start: self.start }),
+            Some(Comment) if self.trivia => self.add_trivia(LineComment, self.position),
+            Some(Blank) => self.add_trivia(Whitespace, self.position),
+            _ => {},
+        }
+
+        // There can still be residue in the buffer, if we haven't encountered a condition
+        // which triggers adding to the buffer. We deal with that here:
+        if !self.buffer.is_empty() {
+            self.flush(self.position)?;
+        }
+
+        // Lexer modes must be left before the document ends
+        self.switch_modes();
+        if let Some(active) = self.modes.last() {
+            self.pending.clear();
+            return Err(UnterminatedMode { name: active.mode.name(), start: active.start });
+        }
+
+        // Indentation levels still open are closed by the end of the document
+        for _ in self.indents.drain(..) {
+            self.pending.insert(self.pending.len(), Token {
+                token_type: Dedent,
+                span: Span::new(self.position, self.position),
+                symbol: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// # Indent
+    /// Called at the first token of a line, when indentation is significant.
+    /// Compares the indentation of the ``line`` with the enclosing levels,
+    /// and adds ``Indent`` or ``Dedent`` tokens accordingly.
+    fn indent(&mut self, line: LineIndentation) -> Result<(), TokenizeError> {
+        let position: Position = self.position;
+
+        if let Some(mixed) = line.mixed {
+            return Err(MixedIndentation { position: mixed });
+        }
+        self.indent_char = self.indent_char.or(line.character);
+
+        let mut token_types: Vec<TokenType> = vec![];
+        if line.width > self.indents.last().copied().unwrap_or(0) {
+            self.indents.insert(self.indents.len(), line.width);
+            token_types.insert(token_types.len(), Indent);
+        }
+        while line.width < self.indents.last().copied().unwrap_or(0) {
+            self.indents.pop();
+            token_types.insert(token_types.len(), Dedent);
+        }
+
+        // A dedent must return to one of the enclosing levels
+        if line.width != self.indents.last().copied().unwrap_or(0) {
+            return Err(InconsistentDedent { position });
+        }
+
+        for token_type in token_types {
+            self.pending.insert(self.pending.len(), Token {
+                token_type,
+                span: Span::new(position, position),
+                symbol: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// # Context none
+    /// Determine what should happen with the stream and context
+    /// based on the character at the cursor. The rest of the document,
+    /// starting from the cursor, is used to look for operators spanning
+    /// multiple characters.
+    ///
+    /// Returns the number of characters consumed.
+    fn context_none(&mut self) -> Result<usize, TokenizeError> {
+        let langdef: &LanguageDefinition = self.langdef;
+        let rest: &str = &self.input[self.cursor..];
+        let position: Position = self.position;
+        let e: char = rest.chars().next().unwrap_or(' ');
+
+        // Nothing but whitespace can follow the line continuation, up to the line break
+        if let Some((character, position)) = self.continuation {
+            if e != '\n' && !langdef.is_whitespace(e) {
+                return Err(InvalidCharacter { character, position });
+            }
+        }
+
+        // A word which switches the lexer mode ends here, and the
+        // character is looked at again with the rules of the new mode
+        if !self.buffer.is_empty() && !Self::is_word_character(langdef, &self.buffer, e) && self.switches_mode(&self.buffer) {
+            self.flush(position)?;
+            return Ok(0);
+        }
+
+        // Decimal points and the signs of exponents continue a number,
+        // rather than being punctuators or operators
+        if Self::continues_number(langdef, &self.buffer, rest) {
+            if self.buffer.is_empty() {
+                self.start = position;
+            }
+            self.buffer.push(e);
+            return Ok(1);
+        }
+
+        // Operators are matched by the longest possible match
+        if let Some(operator) = self.operators.find(rest) {
+            self.flush(position)?;
+            let mut end: Position = position;
+            operator.chars().for_each(|c| end.advance(c));
+            self.pending.insert(self.pending.len(), Token {
+                token_type: Operator(Cow::Borrowed(operator)),
+                span: Span::new(position, end),
+                symbol: None,
+            });
+            return Ok(operator.chars().count());
+        }
+
+        // The span covered by the character itself
+        let mut end: Position = position;
+        end.advance(e);
+        let span: Span = Span::new(position, end);
+
+        match e {
+            // Punctuators:
+            _ if langdef.has_punctuator(e) => {
+                self.flush(position)?;
+                match e {
+                    '(' | '[' | '{' => self.brackets.insert(self.brackets.len(), e),
+                    ')' | ']' | '}' => {
+                        self.brackets.pop();
+                    },
+                    _ => {},
+                }
+                self.pending.insert(self.pending.len(), Token {
+                    token_type: Punctuator(e),
+                    span,
+                    symbol: None,
+                });
+            },
+
+            // Enter Quote context, remembering where the literal starts
+            _ if e == '"' || Some(e) == langdef.char_delimiter() => {
+                self.flush(position)?;
+                self.start = position;
+                self.context = Some(Quotes(e));
+            },
+
+            // Line feeds are added to the stream, when they end a statement
+            // or trivia is kept. A line feed ending a statement begins a new line,
+            // of which the indentation is measured
+            '\n' => {
+                self.flush(position)?;
+                let significant: bool = self.is_line_break();
+                self.continuation = None;
+                if significant {
+                    self.indentation = Some(LineIndentation::default());
+                }
+                if significant || self.trivia {
+                    self.pending.insert(self.pending.len(), Token {
+                        token_type: Newline,
+                        span,
+                        symbol: None,
+                    });
+                }
+            },
+
+            // The line continuation is left out of the stream, unless trivia is kept
+            _ if Some(e) == langdef.line_continuation() && langdef.has_significant_newlines() => {
+                self.flush(position)?;
+                if self.trivia {
+                    self.pending.insert(self.pending.len(), Token {
+                        token_type: LineContinuation(e),
+                        span,
+                        symbol: None,
+                    });
+                }
+                self.continuation = Some((e, position));
+            },
+
+            // Whitespace, such as spaces, tabs and line ends
+            _ if langdef.is_whitespace(e) => {
+                self.flush(position)?;
+                if self.trivia {
+                    self.start = position;
+                    self.context = Some(Blank);
+                    self.keep_trivia(e);
+                }
+            },
+
+            // Characters which can't be part of an identifier or a number
+            // have no place in the document
+            _ if !Self::is_word_character(langdef, &self.buffer, e) => {
+                return Err(InvalidCharacter { character: e, position });
+            },
+
+            // In all other cases, add the character to the buffer, and take
+            // no additional actions
+            _ => {
+                if self.buffer.is_empty() {
+                    self.start = position;
+                }
+                self.buffer.push(e);
+            },
+        }
+
+        Ok(1)
+    }
+
+    /// # Context quotes
+    /// Helper function for when the cursor is between two
+    /// quotation marks (" or the delimiter of character literals),
+    /// including inside escape sequences
+    fn context_quotes(&mut self, char: char) -> Result<(), TokenizeError> {
+        let langdef: &LanguageDefinition = self.langdef;
+        let position: Position = self.position;
+
+        // Following a backslash, we collect characters until they form
+        // a complete (or an invalid) escape sequence
+        if let Some(Escape { delimiter, sequence, start: escape_start }) = &mut self.context {
+            // The delimiter ends an unfinished escape sequence, rather than being part of it
+            if char == *delimiter && !sequence.is_empty() {
+                return Err(InvalidEscape {
+                    sequence: format!("\\{}", sequence),
+                    position: *escape_start,
+                });
+            }
+
+            sequence.push(char);
+            match Self::parse_escape(langdef, sequence) {
+                Ok(Some(decoded)) => {
+                    self.buffer.push(decoded);
+                    self.context = Some(Quotes(*delimiter));
+                },
+                Ok(None) => {},
+                Err(()) => return Err(InvalidEscape {
+                    sequence: format!("\\{}", sequence),
+                    position: *escape_start,
+                }),
+            }
+            return Ok(());
+        }
+
+        let delimiter: char = match self.context {
+            Some(Quotes(delimiter)) => delimiter,
+            _ => '"',
+        };
+
+        match char {
+            '\\' if !langdef.escapes().is_empty() => {
+                self.context = Some(Escape { delimiter, sequence: String::new(), start: position });
+            },
+            _ if char == delimiter => {
+                let mut end: Position = position;
+                end.advance(char);
+                let span: Span = Span::new(self.start, end);
+
+                let kind: LiteralKind = if delimiter == '"' { LiteralKind::String } else { LiteralKind::Char };
+                if kind == LiteralKind::Char && self.buffer.chars().count() != 1 {
+                    return Err(MalformedCharacter { value: self.buffer.clone(), span });
+                }
+
+                // The text between the delimiters
+                let from: usize = self.start.offset + delimiter.len_utf8();
+                let text: Cow<'src, str> = Self::text(self.source, &self.buffer, from, position.offset);
+
+                self.pending.insert(self.pending.len(), Token {
+                    token_type: Literal(kind, text),
+                    span,
+                    symbol: None,
+                });
+                self.buffer.clear();
+                self.context = None;
+            },
+            _ => {
+                self.buffer.push(char);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Keep trivia
+    /// Collect the character in the buffer, when trivia is kept
+    fn keep_trivia(&mut self, char: char) {
+        if self.trivia {
+            self.buffer.push(char);
+        }
+    }
+
+    /// # Add trivia
+    /// Add the trivia collected in the buffer, from ``start`` up to ``end``,
+    /// to the stream as the given ``TokenType``
+    fn add_trivia(&mut self, token_type: fn(Cow<'src, str>) -> TokenType<'src>, end: Position) {
+        let text: Cow<'src, str> = Self::text(self.source, &self.buffer, self.start.offset, end.offset);
+        self.pending.insert(self.pending.len(), Token {
+            token_type: token_type(text),
+            span: Span::new(self.start, end),
+            symbol: None,
+        });
+        self.buffer.clear();
+    }
+
+    /// # Context docblock
+    /// Helper function for when the cursor is inside a docblock.
+    /// The docblock ends at its closer, unless docblocks opened inside
+    /// it (when nesting is allowed) are still open.
+    ///
+    /// Returns the number of characters consumed.
+    fn context_docblock(&mut self) -> usize {
+        let (opener, closer, nested, documentation) = match &mut self.context {
+            Some(DocBlock { opener, closer, nested, documentation }) => (*opener, *closer, nested, *documentation),
+            _ => return 1,
+        };
+
+        // The text consumed at the cursor, and whether it closes the docblock
+        let rest: &str = &self.input[self.cursor..];
+        let (text, closed): (&str, bool) = if !closer.is_empty() && rest.starts_with(closer) {
+            (closer, nested.pop().is_none())
+        } else if self.langdef.has_nested_comments() && !opener.is_empty() && rest.starts_with(opener) {
+            nested.insert(nested.len(), self.position);
+            (opener, false)
+        } else {
+            (&rest[..rest.chars().next().map_or(0, char::len_utf8)], false)
+        };
+
+        let consumed: usize = text.chars().count();
+        let mut end: Position = self.position;
+        text.chars().for_each(|c| end.advance(c));
+        if documentation || self.trivia {
+            self.buffer.push_str(text);
+        }
+
+        if closed {
+            if documentation {
+                self.add_doc_comment(opener, closer, end);
+            } else if self.trivia {
+                self.add_trivia(BlockComment, end);
+            }
+            self.context = None;
+        }
+
+        consumed
+    }
+
+    /// # Context raw
+    /// Helper function for when the cursor is inside a raw lexer mode.
+    /// The text is collected until the closer (unless a backslash precedes it),
+    /// and then added to the stream as an ``Embedded`` token.
+    ///
+    /// Returns the number of characters consumed.
+    fn context_raw(&mut self) -> usize {
+        let (name, closer) = match self.context {
+            Some(Raw { name, closer }) => (name, closer),
+            _ => return 1,
+        };
+
+        let rest: &str = &self.input[self.cursor..];
+        let escaped: bool = self.buffer.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1;
+        if closer.is_empty() || !rest.starts_with(closer) || escaped {
+            self.buffer.extend(rest.chars().next());
+            return 1;
+        }
+
+        let mut end: Position = self.position;
+        closer.chars().for_each(|c| end.advance(c));
+        let text: Cow<'src, str> = Self::text(self.source, &self.buffer, self.start.offset, self.position.offset);
+
+        self.pending.insert(self.pending.len(), Token {
+            token_type: Embedded(name, text),
+            span: Span::new(self.start, end),
+            symbol: None,
+        });
+        self.buffer.clear();
+        self.context = None;
+
+        closer.chars().count()
+    }
+
+    /// # Switch modes
+    /// Called with the tokens found by a step. After the opener of a lexer mode,
+    /// the rules of the mode are used, until the closer of the mode is
+    /// found outside the brackets opened in it
+    fn switch_modes(&mut self) {
+        for index in 0..self.pending.len() {
+            let token: &Token = &self.pending[index];
+            let closes: bool = match self.modes.last() {
+                Some(ActiveMode { mode: LexerMode::Language { closer, .. }, brackets, .. }) => {
+                    token.token_type == *closer && self.brackets.len() <= *brackets
+                },
+                _ => false,
+            };
+
+            if closes {
+                if let Some(active) = self.modes.pop() {
+                    self.langdef = active.outer;
+                    self.operators = OperatorTable::new(active.outer);
+                }
+                continue;
+            }
+
+            let langdef: &'l LanguageDefinition = self.langdef;
+            match langdef.modes().iter().find(|mode| *mode.opener() == token.token_type) {
+                Some(mode @ LexerMode::Language { language, .. }) => {
+                    self.modes.insert(self.modes.len(), ActiveMode {
+                        mode,
+                        outer: langdef,
+                        brackets: self.brackets.len(),
+                        start: token.span.start,
+                    });
+                    self.langdef = language;
+                    self.operators = OperatorTable::new(language);
+                },
+                Some(LexerMode::Raw { name, closer, .. }) => {
+                    self.start = token.span.end;
+                    self.context = Some(Raw { name, closer });
+                },
+                None => {},
+            }
+        }
+    }
+
+    /// # Switches mode
+    /// Returns true, if the word is the opener of a lexer mode,
+    /// or the closer of the current one
+    fn switches_mode(&self, word: &str) -> bool {
+        let closer: Option<&TokenType> = match self.modes.last() {
+            Some(ActiveMode { mode: LexerMode::Language { closer, .. }, .. }) => Some(closer),
+            _ => None,
+        };
+
+        self.langdef.modes()
+            .iter()
+            .map(LexerMode::opener)
+            .chain(closer)
+            .any(|token_type| matches!(token_type, Keyword(text) | Identifier(text) if text == word))
+    }
+
+    /// # Add doc comment
+    /// Called at the closer of a doc comment. The text between the opener
+    /// (followed by its last character once more, like ``/**``) and the closer
+    /// is added to the stream as a ``DocComment``, spanning up to ``end``.
+    fn add_doc_comment(&mut self, opener: &str, closer: &str, end: Position) {
+        let prefix: usize = opener.len() + opener.chars().next_back().map_or(0, char::len_utf8);
+
+        // The buffer holds the entire doc comment
+        self.buffer.truncate(self.buffer.len() - closer.len());
+        self.buffer.drain(..prefix);
+        let text: Cow<'src, str> = Self::text(self.source, &self.buffer, self.start.offset + prefix, end.offset - closer.len());
+
+        self.pending.insert(self.pending.len(), Token {
+            token_type: DocComment(text),
+            span: Span::new(self.start, end),
+            symbol: None,
+        });
+        self.buffer.clear();
+    }
+
+    /// # Is blank
+    /// Returns true for the characters which make up ``Whitespace`` trivia,
+    /// which is all whitespace besides line feeds
+    fn is_blank(&self, char: char) -> bool {
+        char != '\n' && self.langdef.is_whitespace(char)
+    }
+
+    /// # Is line break
+    /// Returns true, if a line feed found now ends the statement.
+    /// The statement continues on the next line inside parentheses and square brackets
+    /// (or any bracket, with significant indentation), after a binary operator,
+    /// and after the line continuation
+    fn is_line_break(&self) -> bool {
+        let langdef: &LanguageDefinition = self.langdef;
+        if !langdef.has_significant_newlines() {
+            return false;
+        }
+
+        let bracketed: bool = match self.brackets.last() {
+            Some('(') | Some('[') => true,
+            Some(_) => langdef.has_significant_indentation(),
+            None => false,
+        };
+
+        let continues: bool = match self.pending.iter().rev().find(|token| Self::is_code(token)) {
+            _ if self.continuation.is_some() => true,
+            Some(token) => Self::continues_line(langdef, &token.token_type),
+            None => self.line_continues,
+        };
+
+        !bracketed && !continues
+    }
+
+    /// # Is code
+    /// Returns true for the tokens which decide whether a line continues,
+    /// leaving out trivia, doc comments and the tokens of the layout
+    fn is_code(token: &Token) -> bool {
+        !token.token_type.is_trivia() && !matches!(token.token_type, Newline | Indent | Dedent | DocComment(_))
+    }
+
+    /// # Continues line
+    /// Returns true, if a line ending with the token continues on the
+    /// next line, which is the case for binary operators
+    fn continues_line(langdef: &LanguageDefinition, token_type: &TokenType) -> bool {
+        matches!(token_type, Operator(operator) if langdef.infix_binding_power(operator).is_some())
+    }
+
+    /// # Is word character
+    /// Returns true, if the character can be added to the buffer, as part of
+    /// an identifier (following the rules of the ``LanguageDefinition``), or
+    /// as part of a number, which begins with a digit
+    fn is_word_character(
+        langdef: &LanguageDefinition,
+        buffer: &str,
+        char: char,
+    ) -> bool {
+        match buffer.chars().next() {
+            None => langdef.is_identifier_start(char) || char.is_ascii_digit(),
+            Some(first) if first.is_ascii_digit() || first == '.' => char.is_ascii_alphanumeric() || char == '_',
+            Some(_) => langdef.is_identifier_continue(char),
+        }
+    }
+
+    /// # Parse escape
+    /// Decode the characters following a backslash, based on the escape
+    /// sequences in the ``LanguageDefinition``.
+    /// Returns ``Ok(None)`` when the sequence is incomplete, but could still
+    /// become valid, and ``Err`` when it can never become valid.
+    fn parse_escape(
+        langdef: &LanguageDefinition,
+        sequence: &str,
+    ) -> Result<Option<char>, ()> {
+        let first: char = sequence.chars().next().ok_or(())?;
+        let rest: &str = &sequence[first.len_utf8()..];
+        let is_hex = |digits: &str| digits.chars().all(|c| c.is_ascii_hexdigit());
+        let decode = |digits: &str| u32::from_str_radix(digits, 16).ok().and_then(char::from_u32);
+
+        for escape in langdef.escapes() {
+            match escape {
+                EscapeSequence::Char(c, replacement) if *c == first => {
+                    return Ok(Some(*replacement));
+                },
+
+                // \u{XXXX}
+                EscapeSequence::Unicode if first == 'u' => {
+                    if rest.is_empty() {
+                        return Ok(None);
+                    }
+                    let digits: &str = rest.strip_prefix('{').ok_or(())?;
+                    return match digits.strip_suffix('}') {
+                        Some(hex) if !hex.is_empty() && hex.len() <= 6 && is_hex(hex) => {
+                            decode(hex).map(Some).ok_or(())
+                        },
+                        None if digits.len() <= 6 && is_hex(digits) => Ok(None),
+                        _ => Err(()),
+                    };
+                },
+
+                // \xNN
+                EscapeSequence::Hex if first == 'x' => {
+                    return match rest.len() {
+                        _ if !is_hex(rest) => Err(()),
+                        0 | 1 => Ok(None),
+                        _ => decode(rest).map(Some).ok_or(()),
+                    };
+                },
+
+                _ => {},
+            }
+        }
+
+        Err(())
+    }
+
+    /// # Flush
+    /// Helper function to streamline the actions taken, when we want to add
+    /// the contents of the buffer (from ``start`` up to ``end``) to the Token Stream
+    fn flush(&mut self, end: Position) -> Result<(), TokenizeError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let span: Span = Span::new(self.start, end);
+        let text: Cow<'src, str> = Self::text(self.source, &self.buffer, span.start.offset, span.end.offset);
+        let keyword_symbols: bool = self.keyword_symbols && self.modes.is_empty();
+        let output: Option<(TokenType<'src>, Option<Symbol>)> = Self::parse_token_type(self.langdef, self.interner.as_mut(), keyword_symbols, text);
+
+        // A buffer beginning like a number, which couldn't be recognized
+        // as a literal, is a number gone wrong, for example ``1.2.3`` or ``0x``
+        if let Some((Identifier(value), _)) = &output {
+            if Self::is_numeric(value) {
+                return Err(MalformedNumber { value: value.to_string(), span });
+            }
+        }
+
+        if let Some((token_type, symbol)) = output {
+            self.pending.insert(self.pending.len(), Token {
+                token_type,
+                span,
+                symbol,
+            });
+        }
+        self.buffer.clear();
+
+        Ok(())
+    }
+
+    /// # Text
+    /// Returns the text of a token, found between the byte offsets ``from``
+    /// and ``to``. The text borrows from the source, unless the buffer differs
+    /// from it (for example due to escape sequences) or there's no source.
+    fn text(
+        source: Option<&'src str>,
+        buffer: &str,
+        from: usize,
+        to: usize,
+    ) -> Cow<'src, str> {
+        match source.and_then(|source| source.get(from..to)) {
+            Some(slice) if slice == buffer => Cow::Borrowed(slice),
+            _ => Cow::Owned(buffer.to_string()),
+        }
+    }
+
+    /// # Parse token type
+    /// Based on the looks of the text, we will return
+    /// a ``TokenType`` enum. Examples include ``Literal``, ``Operator``
+    /// and ``Identifier``.
+    /// With an ``interner``, keywords and identifiers are interned,
+    /// and their ``Symbol`` is returned along with the type. Keywords are
+    /// recognized by their symbol with ``keyword_symbols``, which requires
+    /// the interner to be created from the ``langdef``.
+    fn parse_token_type(
+        langdef: &LanguageDefinition,
+        interner: Option<&mut Interner>,
+        keyword_symbols: bool,
+        text: Cow<'src, str>,
+    ) -> Option<(TokenType<'src>, Option<Symbol>)> {
+        if Self::is_number(langdef, &text) {
+            let is_float: bool = !text.to_ascii_lowercase().starts_with("0x")
+                && text.contains(['.', 'e', 'E']);
+            let kind: LiteralKind = if is_float { LiteralKind::Float } else { LiteralKind::Integer };
+            return Some((Literal(kind, text), None));
+        }
+
+        match &*text {
+            "true" | "false" => return Some((Literal(LiteralKind::Bool, text), None)),
+            "null" => return Some((Literal(LiteralKind::Null, text), None)),
+            _ => {},
+        }
+
+        if text.trim().is_empty() {
+            return None;
+        }
+
+        let symbol: Option<Symbol> = interner.map(|interner| interner.intern(&text));
+        let is_keyword: bool = match symbol {
+            Some(symbol) if keyword_symbols => langdef.is_keyword(symbol),
+            _ => langdef.has_keyword(&text),
+        };
+
+        match is_keyword {
+            true => Some((Keyword(text), symbol)),
+            false => Some((Identifier(text), symbol)),
+        }
+    }
+
+    /// # Is numeric
+    /// Returns true, if the text begins like a number: with a digit,
+    /// or with a dot followed by a digit
+    fn is_numeric(text: &str) -> bool {
+        let mut chars: Chars = text.chars();
+        match chars.next() {
+            Some('.') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => c.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    /// # Continues number
+    /// Returns true, if the character at the beginning of ``rest`` continues
+    /// the number in the buffer (or starts a number like ``.5``), in cases where
+    /// it would otherwise be a punctuator or an operator
+    fn continues_number(
+        langdef: &LanguageDefinition,
+        buffer: &str,
+        rest: &str,
+    ) -> bool {
+        let mut chars: Chars = rest.chars();
+        let (e, next) = (chars.next(), chars.next());
+        let next_is_digit: bool = next.is_some_and(|c| c.is_ascii_digit());
+
+        match e {
+            // The decimal point, or the dot of a decimal without an integer part
+            Some('.') if next_is_digit => {
+                Self::is_numeric(buffer) || (buffer.is_empty() && langdef.has_number_format(NumberFormat::LeadingDot))
+            },
+
+            // The sign of an exponent, such as 1e-9 (but not in hexadecimals like 0xE)
+            Some('+') | Some('-') if next_is_digit => {
+                langdef.has_number_format(NumberFormat::Exponent)
+                    && Self::is_numeric(buffer)
+                    && buffer.ends_with(['e', 'E'])
+                    && !buffer.starts_with("0x")
+                    && !buffer.starts_with("0X")
+            },
+
+            _ => false,
+        }
+    }
+
+    /// # Is number
+    /// Returns true, if the text is a number written in one of the
+    /// notations enabled in the ``LanguageDefinition``
+    fn is_number(
+        langdef: &LanguageDefinition,
+        text: &str,
+    ) -> bool {
+        let separators: bool = langdef.has_number_format(NumberFormat::Separator);
+        let digits = |text: &str, radix: u32| {
+            !text.is_empty()
+                && !text.starts_with('_')
+                && !text.ends_with('_')
+                && text.chars().all(|c| c.is_digit(radix) || (separators && c == '_'))
+        };
+
+        // Integers with a radix prefix, such as 0xFF
+        let prefixes = [
+            ("0x", 16, NumberFormat::Hexadecimal),
+            ("0b", 2, NumberFormat::Binary),
+            ("0o", 8, NumberFormat::Octal),
+        ];
+        for (prefix, radix, format) in prefixes {
+            if text.get(..2).is_some_and(|start| start.eq_ignore_ascii_case(prefix)) {
+                return langdef.has_number_format(format) && digits(&text[2..], radix);
+            }
+        }
+
+        // Decimals, optionally with an exponent
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(index) if langdef.has_number_format(NumberFormat::Exponent) => (&text[..index], Some(&text[index + 1..])),
+            _ => (text, None),
+        };
+
+        if let Some(exponent) = exponent {
+            if !digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent), 10) {
+                return false;
+            }
+        }
+
+        match mantissa.split_once('.') {
+            Some(("", fraction)) => langdef.has_number_format(NumberFormat::LeadingDot) && digits(fraction, 10),
+            Some((integer, fraction)) => digits(integer, 10) && digits(fraction, 10),
+            None => digits(mantissa, 10),
+        }
+    }
+}
+
+impl<'src> Iterator for Lexer<'_, 'src> {
+    type Item = Result<Token<'src>, TokenizeError>;
+
+    fn next(&mut self) -> Option<Result<Token<'src>, TokenizeError>> {
+        loop {
+            if !self.pending.is_empty() {
+                return Some(Ok(self.pending.remove(0)));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if let Err(error) = self.step() {
+                self.finished = true;
+                return Some(Err(error));
+            }
+        }
+    }
+}
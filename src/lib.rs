@@ -1,4 +1,9 @@
 pub mod langdef;
 pub mod tokenizer;
+pub mod lexer;
 pub mod composer;
 pub mod fragments;
+pub mod span;
+pub mod grammar;
+pub mod expression;
+pub mod interner;
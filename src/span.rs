@@ -0,0 +1,96 @@
+use std::fmt::{Display, Formatter, Result};
+
+/// # Position
+/// A single location in the source document, expressed both as a
+/// byte offset and as a line and column (both starting from 1)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// # New position
+    /// Create a ``Position`` from its byte offset, line and column
+    pub fn new(offset: usize, line: usize, column: usize) -> Position {
+        Position { offset, line, column }
+    }
+
+    /// # Start position
+    /// The position of the very first character in a document
+    pub fn start() -> Position {
+        Position::new(0, 1, 1)
+    }
+
+    /// # Advance
+    /// Move the position past the character ``c``, stepping to the
+    /// next line when the character is a line feed
+    pub fn advance(&mut self, c: char) {
+        self.offset += c.len_utf8();
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position::start()
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// # Span
+/// The area of the source document covered by a token or fragment.
+/// The ``start`` position is inclusive, while ``end`` is exclusive.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// # New span
+    /// Create a ``Span`` between two positions
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    /// # Join
+    /// Returns a ``Span`` covering both this and the ``other`` span,
+    /// including anything found in between
+    pub fn join(&self, other: &Span) -> Span {
+        Span {
+            start: if other.start.offset < self.start.offset { other.start } else { self.start },
+            end: if other.end.offset > self.end.offset { other.end } else { self.end },
+        }
+    }
+
+    /// # Length
+    /// The number of bytes covered by the span
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    /// # Is empty
+    /// Returns true, if the span doesn't cover any bytes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
@@ -1,17 +1,21 @@
+use crate::interner::{Interner, Symbol};
 use crate::langdef::LanguageDefinition;
+use crate::lexer::Lexer;
+use crate::span::{Position, Span};
 use crate::tokenizer::{
-    Context::*,
+    TokenizeError::*,
     TokenType::*,
 };
 use std::{
-    ops::Add,
-    str::Chars,
+    borrow::Cow,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::ErrorKind,
 };
-use regex::Regex;
 
 /// # TokenStream
 /// A list/stream container of ``Token`` based on ``Vec<Token>``
-pub type TokenStream = Vec<Token>;
+pub type TokenStream<'src> = Vec<Token<'src>>;
 
 /// # Tokenizer struct
 /// The main Tokenizer instance which contains the static
@@ -20,23 +24,33 @@ pub type TokenStream = Vec<Token>;
 pub struct Tokenizer;
 
 /// # Token struct
+/// A single token found in the document, along with the ``Span``
+/// describing where in the document it was found.
 ///
-#[derive(Debug, PartialEq, Clone)]
-pub struct Token {
-    pub token_type: TokenType,
+/// The text of the token borrows from the document (``'src``) whenever
+/// possible. It's only owned when the text differs from the document, such as
+/// strings with escape sequences, or when the document is read from a reader.
+///
+/// Keywords and identifiers carry a ``Symbol`` when the document
+/// is tokenized with an ``Interner``.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Token<'src> {
+    pub token_type: TokenType<'src>,
+    pub span: Span,
+    pub symbol: Option<Symbol>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum TokenType {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum TokenType<'src> {
     /// Keywords are recognized words found in the source code, and outside
     /// contexts such as quotes, docblocks and comments.
     /// Classic examples include ``if``, ``else``, ``match``, ``switch``, ``public``, and so on.
     /// Keywords are specified in the ``langdef``
-    Keyword(String),
+    Keyword(Cow<'src, str>),
 
     /// Identifiers are words found in the code which couldn't be mapped to a
     /// keyword. They are typically names of variables, constants and functions.
-    Identifier(String),
+    Identifier(Cow<'src, str>),
 
     /// Punctuators are structures in the code such as parentheses, brackets and statement endings.
     /// For example: {} () and ;
@@ -44,230 +58,280 @@ pub enum TokenType {
 
     /// Operators are typically arithmetic functions such as addition (+), subtraction (-),
     /// multplication (-), assignment (=) and comparison (==)
-    Operator(String),
-
-    /// A literal can be a string, number, boolean or ``null``.
-    Literal(String),
+    Operator(Cow<'src, str>),
+
+    /// A literal can be a string, number, boolean, character or ``null``.
+    /// The ``LiteralKind`` tells them apart, since the string ``"30"`` and the
+    /// number ``30`` share the same text.
+    Literal(LiteralKind, Cow<'src, str>),
+
+    /// A line break. Newlines are only part of the token stream when the
+    /// language definition makes them significant, for example by using
+    /// them as statement terminator, or when trivia is kept.
+    Newline,
+
+    /// The beginning of a line which is indented deeper than the line before.
+    /// Only part of the token stream with significant indentation.
+    Indent,
+
+    /// The beginning of a line which is indented shallower than the line before.
+    /// One ``Dedent`` is added for every indentation level which ends.
+    Dedent,
+
+    /// Whitespace between tokens, such as spaces, tabs and carriage returns.
+    /// Only part of the token stream when trivia is kept.
+    Whitespace(Cow<'src, str>),
+
+    /// A comment running to the end of the line, including the ``//`` or ``#``.
+    /// Only part of the token stream when trivia is kept.
+    LineComment(Cow<'src, str>),
+
+    /// A docblock, including the ``/*`` and ``*/``.
+    /// Only part of the token stream when trivia is kept.
+    BlockComment(Cow<'src, str>),
+
+    /// The line continuation, such as ``\``, at the end of a line.
+    /// Only part of the token stream when trivia is kept.
+    LineContinuation(char),
+
+    /// The text of a docblock beginning with ``/**``, which documents the code
+    /// following it. Unlike other docblocks, these are always part of the token stream,
+    /// and the ``Composer`` attaches them to the next statement or encapsulation.
+    DocComment(Cow<'src, str>),
+
+    /// The text of a raw lexer mode, such as a regular expression,
+    /// along with the name of the mode. The text doesn't include the closer.
+    Embedded(&'static str, Cow<'src, str>),
 }
 
-#[derive(Debug, PartialEq)]
-enum Context {
-    Quotes,
-    Comment,
-    DocBlock,
+/// # Literal kind
+/// The different kinds of literals
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum LiteralKind {
+    String,
+    Integer,
+    Float,
+    Bool,
+    Null,
+    Char,
 }
 
-impl Tokenizer {
-    /// # Tokenize
-    /// Build a ``TokenStream`` based on the passed document and ``LanguageDefinition``.
-    /// The function iterates over the document character by character, and slices the
-    /// content into tokens such as keywords, literals and punctuators.
-    ///
-    /// A Result will be returned containing either an error (typically based on syntax)
-    /// or a ``TokenStream`` which is essentially an ordered ``Vec`` containing the tokens.
-    ///
-    /// Comments and docblocks are ignored.
-    pub fn tokenize(
-        langdef: &LanguageDefinition,
-        document: String,
-    ) -> Result<TokenStream, ()> {
-        let mut stream: TokenStream = TokenStream::new();
-
-        // This variable is to remember if we have entered a certain context,
-        // for example inside quoted strings or comments, which require different
-        // handling than other scenarios
-        let mut context: Option<Context> = None;
-
-        // The buffer holds none, one or several characters, which are picked up,
-        // until we figure out what to do with them
-        let mut buffer: String = String::new();
-
-        // Shorthand to determine if we have encountered the end of the line
-        let mut is_eol: bool;
-
-        // Look at the next (peek) and previous characters in the document
-        let mut peek: char;
-        let mut prev: Option<char> = None;
-
-        // Regular iterator/counter to define at which index we are in
-        // the complete document
-        let mut i: usize = 0;
-
-        // The entire document separated into single characters
-        let chars: Chars = document.chars();
-
-        for e in document.chars() {
-            is_eol = e == '\n' || e == '\r';
-
-            // Store the next character ("peek") for analysis
-            peek = chars.clone().nth(i + 1).unwrap_or(' ');
-
-            // When there's no context and the current and next character form /*
-            // we're entering a docblock
-            if context.is_none() && e == '/' && peek == '*' {
-                context = Some(DocBlock);
-
-            // If we are in docblock context and encounter */, which indicates the end
-            // of a docblock, we leave that context here.
-            // Since docblocks should be ignored, we will not do anything with
-            // eventual buffer content
-            } else if context.is_some() && prev.is_some() && context.as_ref().unwrap() == &DocBlock && e == '/' && prev.unwrap() == '*' {
-                context = None;
-
-            // If we aren't in an established context, but encounter //, we will
-            // enter a comment context (which is also just to be ignored)
-            } else if context.is_none() && e == '/' && peek == '/' {
-                context = Some(Comment);
-
-            // When we are inside a quote context, we want to add the character to the
-            // the buffer, unless it's a quote, in which case we leave quote context
-            // @todo: Implement escaping of quotes (using prev variable)
-            } else if context.is_some() && context.as_ref().unwrap() == &Quotes {
-                Self::context_quotes(e, &mut stream, &mut buffer, &mut context);
-
-            // We ignore docblock context, therefore no actions are taken, besides
-            // making sure we enter a scope with no actions defined
-            } else if context.is_some() && context.as_ref().unwrap() == &DocBlock {
-
-            // Ordinary comments (// and #) are terminated when encountering the end of the line
-            } else if context.is_some() && context.as_ref().unwrap() == &Comment && is_eol {
-                context = None;
-
-            // When there's no defined context, we will use a match pattern to decide what
-            // should happen, based on which character we've seen
-            } else if context.is_none() {
-                Self::context_none(&langdef, e, &mut stream, &mut buffer, &mut context);
-            }
-
-            prev = Some(e);
-            i = i + 1;
-        }
-
-        // There can still be residue in the buffer, if we haven't encountered a condition
-        // which triggers adding to the buffer. We deal with that here:
-        if !buffer.is_empty() {
-            Self::add_to_stream(&langdef, &mut stream, &mut buffer);
+impl TokenType<'_> {
+    /// # Into owned
+    /// Returns the token type with an owned text, which no longer
+    /// borrows from the document
+    pub fn into_owned(self) -> TokenType<'static> {
+        match self {
+            Keyword(text) => Keyword(Cow::Owned(text.into_owned())),
+            Identifier(text) => Identifier(Cow::Owned(text.into_owned())),
+            Punctuator(char) => Punctuator(char),
+            Operator(text) => Operator(Cow::Owned(text.into_owned())),
+            Literal(kind, text) => Literal(kind, Cow::Owned(text.into_owned())),
+            Newline => Newline,
+            Indent => Indent,
+            Dedent => Dedent,
+            Whitespace(text) => Whitespace(Cow::Owned(text.into_owned())),
+            LineComment(text) => LineComment(Cow::Owned(text.into_owned())),
+            BlockComment(text) => BlockComment(Cow::Owned(text.into_owned())),
+            LineContinuation(char) => LineContinuation(char),
+            DocComment(text) => DocComment(Cow::Owned(text.into_owned())),
+            Embedded(name, text) => Embedded(name, Cow::Owned(text.into_owned())),
         }
-
-        Ok(stream)
     }
 
-    /// # Context none
-    /// Determine what should happen with the stream and context
-    /// based on the character at the pointer
-    fn context_none(
-        langdef: &LanguageDefinition,
-        e: char,
-        stream: &mut TokenStream,
-        buffer: &mut String,
-        context: &mut Option<Context>,
-    ) {
-        match e {
-            // Punctuators:
-            ';' | '{' | '}' | '(' | ')' | '[' | ']' => {
-                Self::add_to_stream(&langdef, stream, buffer);
-                stream.insert(stream.len(), Token {
-                    token_type: Punctuator(e),
-                });
-            },
-
-            // Operators:
-            '+' | '-' | '/' | '*' | '%' => {
-                Self::add_to_stream(&langdef, stream, buffer);
-                stream.insert(stream.len(), Token {
-                    token_type: Operator(e.to_string()),
-                });
-            },
+    /// # Is trivia
+    /// Returns true for whitespace and comments, which carry no meaning
+    /// in the language. Newlines aren't included, since they can be significant.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Whitespace(_) | LineComment(_) | BlockComment(_) | LineContinuation(_))
+    }
+}
 
-            // Comment
-            '#' => {
-                *context = Some(Comment);
-            },
+impl Token<'_> {
+    /// # Into owned
+    /// Returns the token with an owned text, which no longer
+    /// borrows from the document
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            token_type: self.token_type.into_owned(),
+            span: self.span,
+            symbol: self.symbol,
+        }
+    }
 
-            // Enter Quote context
-            '"' => {
-                *context = Some(Quotes);
-            },
+    /// # Literal kind
+    /// Returns the kind of literal, if the token is a literal
+    pub fn literal_kind(&self) -> Option<LiteralKind> {
+        match &self.token_type {
+            Literal(kind, _) => Some(*kind),
+            _ => None,
+        }
+    }
 
-            // Space or end of line
-            ' ' | '\n' | '\r' => {
-                Self::add_to_stream(&langdef, stream, buffer);
+    /// # As integer
+    /// Returns the value of integer literals, including those written in
+    /// hexadecimal, binary and octal notation. ``None`` is returned for other
+    /// tokens, and for integers which don't fit in an ``i64``.
+    pub fn as_integer(&self) -> Option<i64> {
+        match &self.token_type {
+            Literal(LiteralKind::Integer, value) => {
+                let digits: String = value.replace('_', "");
+                let (radix, digits) = match digits.get(..2).map(|prefix| prefix.to_ascii_lowercase()).as_deref() {
+                    Some("0x") => (16, &digits[2..]),
+                    Some("0b") => (2, &digits[2..]),
+                    Some("0o") => (8, &digits[2..]),
+                    _ => (10, &digits[..]),
+                };
+                i64::from_str_radix(digits, radix).ok()
             },
+            _ => None,
+        }
+    }
 
-            // In all other cases, add the character to the buffer, and take
-            // no additional actions
-            _ => {
-                *buffer = buffer.to_string().add(e.to_string().as_str());
-            },
+    /// # As float
+    /// Returns the value of numeric literals (both floats and integers)
+    /// as an ``f64``
+    pub fn as_float(&self) -> Option<f64> {
+        match &self.token_type {
+            Literal(LiteralKind::Float, value) => value.replace('_', "").parse().ok(),
+            Literal(LiteralKind::Integer, _) => self.as_integer().map(|value| value as f64),
+            _ => None,
         }
     }
 
-    /// # Context quotes
-    /// Helper function for when the cursor is between two
-    /// quotation marks (")
-    fn context_quotes(
-        char: char,
-        stream: &mut TokenStream,
-        buffer: &mut String,
-        context: &mut Option<Context>,
-    ) {
-        match char {
-            '"' => {
-                stream.insert(stream.len(), Token {
-                    token_type: Literal(buffer.clone()),
-                });
-                *buffer = String::new();
-                *context = None;
-            },
-            _ => {
-                *buffer = buffer.to_string().add(char.to_string().as_str());
-            }
+    /// # As bool
+    /// Returns the value of ``true`` and ``false`` literals
+    pub fn as_bool(&self) -> Option<bool> {
+        match &self.token_type {
+            Literal(LiteralKind::Bool, value) => Some(value == "true"),
+            _ => None,
         }
     }
 
-    /// # Add to stream
-    /// Helper function to streamline the actions taken, when we want
-    /// add the contents of the buffer to the Token Stream
-    fn add_to_stream(
-        langdef: &LanguageDefinition,
-        stream: &mut TokenStream,
-        buffer: &mut String,
-    ) {
-        let output: Option<TokenType> = Self::parse_token_type(&langdef, &buffer);
-        if output.is_some() {
-            stream.insert(stream.len(), Token {
-                token_type: output.unwrap(),
-            });
+    /// # As char
+    /// Returns the value of character literals
+    pub fn as_char(&self) -> Option<char> {
+        match &self.token_type {
+            Literal(LiteralKind::Char, value) => value.chars().next(),
+            _ => None,
         }
-        *buffer = String::new();
     }
+}
 
-    /// # Parse token type
-    /// Based on the looks of the buffer content, we will return
-    /// a ``TokenType`` enum. Examples include ``Literal``, ``Operator``
-    /// and ``Identifier``.
-    fn parse_token_type(
-        langdef: &LanguageDefinition,
-        buffer: &String,
-    ) -> Option<TokenType> {
-        let regex_literal: Regex = Regex::new(r"^([0-9]+(\.[0-9]+)?|true|false|null)$").unwrap();
+/// # Tokenize error
+/// The errors which can occur while tokenizing a document.
+/// Each error carries the location in the document where it was found.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TokenizeError {
+    /// A quoted string was opened at ``start``, but never closed
+    UnterminatedString { start: Position },
 
-        if regex_literal.is_match(buffer.trim()) {
-            return Some(Literal(buffer.clone()));
-        }
+    /// A docblock (``/* ... */``) was opened at ``start``, but never closed.
+    /// With nested docblocks, this is the innermost one left open.
+    UnterminatedDocBlock { start: Position },
 
-        if langdef.has_keyword(buffer) {
-            return Some(Keyword(buffer.clone()));
-        }
+    /// A character which isn't allowed anywhere in the language
+    InvalidCharacter { character: char, position: Position },
 
-        if buffer == "=" || buffer == "==" {
-            return Some(Operator(buffer.to_string()));
-        }
+    /// A value which starts like a number, but isn't a valid one
+    MalformedNumber { value: String, span: Span },
+
+    /// A character literal which doesn't contain exactly one character
+    MalformedCharacter { value: String, span: Span },
+
+    /// An escape sequence inside a quoted string, beginning at ``position``,
+    /// which isn't recognized by the language definition
+    InvalidEscape { sequence: String, position: Position },
+
+    /// The document couldn't be read, or isn't valid UTF-8
+    Io { kind: ErrorKind, message: String },
+
+    /// A line, beginning with the token at ``position``, is indented shallower than
+    /// the line before, but doesn't match any of the enclosing indentation levels
+    InconsistentDedent { position: Position },
 
-        if buffer.trim().is_empty() {
-            return None;
+    /// Tabs and spaces are both used for indentation, with the first
+    /// inconsistent character at ``position``
+    MixedIndentation { position: Position },
+
+    /// A lexer mode (``name``) was entered at ``start``, but never left
+    UnterminatedMode { name: &'static str, start: Position },
+}
+
+impl Display for TokenizeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UnterminatedString { start } => write!(f, "Unterminated string starting at {}", start),
+            UnterminatedDocBlock { start } => write!(f, "Unterminated docblock starting at {}", start),
+            InvalidCharacter { character, position } => write!(f, "Invalid character {:?} at {}", character, position),
+            MalformedNumber { value, span } => write!(f, "Malformed number \"{}\" at {}", value, span.start),
+            MalformedCharacter { value, span } => write!(f, "Malformed character literal '{}' at {}", value, span.start),
+            InvalidEscape { sequence, position } => write!(f, "Invalid escape sequence \"{}\" at {}", sequence, position),
+            Io { message, .. } => write!(f, "Unable to read the document: {}", message),
+            InconsistentDedent { position } => write!(f, "Indentation doesn't match any outer level at {}", position),
+            MixedIndentation { position } => write!(f, "Inconsistent use of tabs and spaces in indentation at {}", position),
+            UnterminatedMode { name, start } => write!(f, "Unterminated {} starting at {}", name, start),
         }
+    }
+}
+
+impl Error for TokenizeError {}
 
-        Some(Identifier(buffer.clone()))
+impl Tokenizer {
+    /// # Tokenize
+    /// Build a ``TokenStream`` based on the passed document and ``LanguageDefinition``.
+    /// The function iterates over the document character by character, and slices the
+    /// content into tokens such as keywords, literals and punctuators.
+    ///
+    /// A Result will be returned containing either a ``TokenizeError`` (such as an
+    /// unterminated string) or a ``TokenStream`` which is essentially an ordered ``Vec``
+    /// containing the tokens.
+    ///
+    /// Comments and docblocks are ignored, except docblocks beginning
+    /// with ``/**``, which become ``DocComment`` tokens.
+    ///
+    /// Every token carries a ``Span`` with the byte offset, line and column
+    /// of where it starts and ends in the document.
+    ///
+    /// To produce the tokens on demand instead, use the ``Lexer``.
+    pub fn tokenize<'src>(
+        langdef: &LanguageDefinition,
+        document: &'src str,
+    ) -> Result<TokenStream<'src>, TokenizeError> {
+        Lexer::new(langdef, document).collect()
+    }
+
+    /// # Tokenize interned
+    /// Works like ``tokenize``, but also stores the text of keywords and
+    /// identifiers in the ``interner``, and gives their tokens a ``Symbol``.
+    /// Identical names then have the same symbol, and can be compared by it.
+    ///
+    /// The interner can be reused for several documents. When it's created with
+    /// ``LanguageDefinition::interner``, keywords are recognized by their symbol.
+    pub fn tokenize_interned<'src>(
+        langdef: &LanguageDefinition,
+        document: &'src str,
+        interner: &mut Interner,
+    ) -> Result<TokenStream<'src>, TokenizeError> {
+        let mut lexer: Lexer = Lexer::new(langdef, document).with_interner(std::mem::take(interner));
+        let result: Result<TokenStream<'src>, TokenizeError> = lexer.by_ref().collect();
+        *interner = lexer.into_interner().unwrap_or_default();
+        result
+    }
+
+    /// # Tokenize with trivia
+    /// Works like ``tokenize``, but keeps whitespace, newlines, comments, docblocks
+    /// and line continuations as ``Whitespace``, ``Newline``, ``LineComment``,
+    /// ``BlockComment`` and ``LineContinuation`` tokens. Nothing is left out, so
+    /// the spans of the tokens cover the entire document, and the document can be
+    /// reproduced byte for byte from them.
+    ///
+    /// This is useful for tools such as formatters, which must preserve
+    /// the comments and layout of the document.
+    pub fn tokenize_with_trivia<'src>(
+        langdef: &LanguageDefinition,
+        document: &'src str,
+    ) -> Result<TokenStream<'src>, TokenizeError> {
+        Lexer::new(langdef, document).with_trivia().collect()
     }
 }
@@ -1,4 +1,4 @@
-#![allow(dead_code, unused_variables)]
+#![allow(dead_code, unused_variables, clippy::unnecessary_cast)]
 
 use std::fs;
 use std::path::Path;
@@ -23,7 +23,7 @@ pub fn test_stream(
 }
 
 pub fn test_statement(
-    statement: &Box<dyn Fragment>,
+    statement: &Fragment,
     assertions: Vec<TokenType>,
 ) {
     for i in 0..assertions.len() {
@@ -1,16 +1,26 @@
+#![allow(clippy::get_first, clippy::needless_borrow)]
+
 mod common;
 
+use std::collections::HashSet;
 use rust_lexical_analyzer::{
     tokenizer::{
         Tokenizer,
+        TokenizeError,
+        LiteralKind,
         TokenType::*,
         TokenStream,
     },
     langdef::LanguageDefinition,
+    span::{Position, Span},
     composer::{
+        BracketType,
         Composer,
+        ComposeError,
+        ComposerContext,
         Composition,
     },
+    fragments::Fragment,
 };
 
 use common::*;
@@ -18,80 +28,381 @@ use common::*;
 #[test]
 fn composer() {
     let langdef: LanguageDefinition = default_langdef();
-    let result: Result<TokenStream, ()> = Tokenizer::tokenize(
+    let document: String = get_test_file("composer_std.txt");
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
         &langdef,
-        get_test_file("composer_std.txt"),
+        &document,
     );
 
-    let composed: Composition = Composer::compose(&langdef, &result.unwrap());
-    let block1: Composition = composed.get(1).unwrap().get_fragments().unwrap();
+    let stream: TokenStream = result.unwrap();
+
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+    let block1: &Composition = composed.get(1).unwrap().get_fragments().unwrap();
 
     test_statement(composed.get(0).unwrap(), vec![
-        Keyword(String::from("if")),
-        Identifier(String::from("x")),
-        Operator(String::from("==")),
-        Literal(String::from("30")),
+        Keyword("if".into()),
+        Identifier("x".into()),
+        Operator("==".into()),
+        Literal(LiteralKind::Integer, "30".into()),
     ]);
 
     test_statement(&block1.get(0).unwrap(), vec![
-        Keyword(String::from("if")),
-        Literal(String::from("true")),
+        Keyword("if".into()),
+        Literal(LiteralKind::Bool, "true".into()),
     ]);
 
     test_statement(&block1.get(1).unwrap().get_fragments().unwrap().get(0).unwrap(), vec![
-        Identifier(String::from("print")),
-        Literal(String::from("Hello world")),
+        Identifier("print".into()),
+        Literal(LiteralKind::String, "Hello world".into()),
     ]);
 
     assert_eq!(
-        Keyword(String::from("else")),
+        Keyword("else".into()),
         block1.get(2).unwrap().get_token().unwrap().token_type,
     );
 
     test_statement(&block1.get(3).unwrap().get_fragments().unwrap().get(0).unwrap(), vec![
-        Identifier(String::from("print")),
-        Literal(String::from("nested else")),
+        Identifier("print".into()),
+        Literal(LiteralKind::String, "nested else".into()),
     ]);
 
     assert_eq!(
-        Keyword(String::from("else")),
+        Keyword("else".into()),
         composed.get(2).unwrap().get_token().unwrap().token_type,
     );
 
     test_statement(&composed.get(3).unwrap().get_fragments().unwrap().get(0).unwrap(), vec![
-        Identifier(String::from("print")),
-        Literal(String::from("else")),
+        Identifier("print".into()),
+        Literal(LiteralKind::String, "else".into()),
     ]);
 }
 
 #[test]
 fn composer_brackets() {
     let expressions = vec![
-        "1 + (2 + (3 * 3));".to_string(),
-        "1 + (2 + [3 * 3]);".to_string(),
-        "1 + [2 + [3 * 3]];".to_string(),
+        "1 + (2 + (3 * 3));",
+        "1 + (2 + [3 * 3]);",
+        "1 + [2 + [3 * 3]];",
     ];
 
     for expr in expressions {
         let langdef: LanguageDefinition = default_langdef();
-        let result: Result<TokenStream, ()> = Tokenizer::tokenize(&langdef, expr);
-        let composed: Composition = Composer::compose(&langdef, &result.unwrap());
-        let b1_fragments: Composition = composed.get(1).unwrap().get_fragments().unwrap();
+        let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, expr);
+        let stream: TokenStream = result.unwrap();
+        let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+        let b1_fragments: &Composition = composed.get(1).unwrap().get_fragments().unwrap();
 
         test_statement(composed.get(0).unwrap(), vec![
-            Literal(String::from("1")),
-            Operator(String::from("+")),
+            Literal(LiteralKind::Integer, "1".into()),
+            Operator("+".into()),
         ]);
 
         test_statement(b1_fragments.get(0).unwrap(), vec![
-            Literal(String::from("2")),
-            Operator(String::from("+")),
+            Literal(LiteralKind::Integer, "2".into()),
+            Operator("+".into()),
         ]);
 
         test_statement(b1_fragments.get(1).unwrap().get_fragments().unwrap().get(0).unwrap(), vec![
-            Literal(String::from("3")),
-            Operator(String::from("*")),
-            Literal(String::from("3")),
+            Literal(LiteralKind::Integer, "3".into()),
+            Operator("*".into()),
+            Literal(LiteralKind::Integer, "3".into()),
         ]);
     }
 }
+
+#[test]
+fn composer_spans() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, "a = 1 + (2 * 3);");
+    let stream: TokenStream = result.unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    // a = 1 +
+    assert_eq!(Span::new(Position::new(0, 1, 1), Position::new(7, 1, 8)), composed[0].get_span());
+
+    // (2 * 3) including the parentheses
+    assert_eq!(Span::new(Position::new(8, 1, 9), Position::new(15, 1, 16)), composed[1].get_span());
+
+    // 2 * 3
+    let inner: &Composition = composed[1].get_fragments().unwrap();
+    assert_eq!(Span::new(Position::new(9, 1, 10), Position::new(14, 1, 15)), inner[0].get_span());
+}
+
+#[test]
+fn composer_borrows_tokens() {
+    let langdef: LanguageDefinition = default_langdef();
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "a = 1 + (2 * 3); else").unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    // The fragments refer to ranges of the stream, rather than copies
+    assert!(std::ptr::eq(composed[0].get_tokens(), &stream[0..4]));
+    assert!(std::ptr::eq(composed[1].get_fragments().unwrap()[0].get_tokens(), &stream[5..8]));
+    assert!(std::ptr::eq(composed[2].get_token().unwrap(), &stream[10]));
+}
+
+#[test]
+fn composer_custom_delimiters() {
+    let langdef: LanguageDefinition = LanguageDefinition::new(vec!["if", "begin", "end", "print"])
+        .with_statement_terminator(Punctuator('.'))
+        .with_block_delimiters(Keyword("begin".into()), Keyword("end".into()));
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "x = 1. if x begin print x. print 2.5. end",
+    );
+    let stream: TokenStream = result.unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    test_statement(composed.first().unwrap(), vec![
+        Identifier("x".into()),
+        Operator("=".into()),
+        Literal(LiteralKind::Integer, "1".into()),
+    ]);
+
+    test_statement(composed.get(1).unwrap(), vec![
+        Keyword("if".into()),
+        Identifier("x".into()),
+    ]);
+
+    let block: &Composition = composed.get(2).unwrap().get_fragments().unwrap();
+    assert_eq!(2, block.len());
+    test_statement(block.get(1).unwrap(), vec![
+        Keyword("print".into()),
+        Literal(LiteralKind::Float, "2.5".into()),
+    ]);
+}
+
+#[test]
+fn composer_newline_terminator() {
+    let langdef: LanguageDefinition = default_langdef().with_statement_terminator(Newline);
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "let x = 1 // first\n\nlet y = 2\n",
+    );
+    let stream: TokenStream = result.unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    assert_eq!(2, composed.len());
+    test_statement(composed.get(1).unwrap(), vec![
+        Keyword("let".into()),
+        Identifier("y".into()),
+        Operator("=".into()),
+        Literal(LiteralKind::Integer, "2".into()),
+    ]);
+}
+
+#[test]
+fn composer_unbalanced_brackets() {
+    let langdef: LanguageDefinition = default_langdef();
+    let compose = |document: &str| {
+        let stream: TokenStream = Tokenizer::tokenize(&langdef, document).unwrap();
+        Composer::compose(&langdef, &stream).map(|_| ())
+    };
+    let span = |offset: usize| Span::new(Position::new(offset, 1, offset + 1), Position::new(offset + 1, 1, offset + 2));
+
+    assert_eq!(
+        ComposeError::MismatchedBracket { opener: span(4), closer: span(10) },
+        compose("1 + (2 + 3];").unwrap_err(),
+    );
+
+    assert_eq!(
+        ComposeError::MismatchedBracket { opener: span(5), closer: span(7) },
+        compose("if (x[1)] {}").unwrap_err(),
+    );
+
+    assert_eq!(
+        ComposeError::StrayCloser { closer: span(6) },
+        compose("x = 1 };").unwrap_err(),
+    );
+
+    assert_eq!(
+        ComposeError::UnclosedBracket { opener: span(7) },
+        compose("if x { (print x;").unwrap_err(),
+    );
+
+    assert_eq!(
+        "Unexpected closing bracket at 1:7",
+        compose("x = 1 };").unwrap_err().to_string(),
+    );
+}
+
+#[test]
+fn composer_fragment_traits() {
+    let langdef: LanguageDefinition = default_langdef();
+    let document: String = get_test_file("composer_std.txt");
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, &document).unwrap();
+    let other: TokenStream = Tokenizer::tokenize(&langdef, "if x == 31 {}").unwrap();
+
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+    assert_eq!(composed, Composer::compose(&langdef, &stream).unwrap());
+    assert_eq!(composed, composed.clone());
+    assert_ne!(composed, Composer::compose(&langdef, &other).unwrap());
+
+    let mut set: HashSet<Composition> = HashSet::new();
+    set.insert(composed.clone());
+    assert!(set.contains(&composed));
+
+    assert!(matches!(composed[0], Fragment::Statement(_)));
+    assert!(matches!(composed[1], Fragment::Encapsulation(_)));
+    assert!(matches!(composed[2], Fragment::SingleToken(_)));
+}
+
+#[test]
+fn composer_doc_comments() {
+    let langdef: LanguageDefinition = default_langdef();
+    let stream: TokenStream = Tokenizer::tokenize(
+        &langdef,
+        "/** Declares x */ let x = 1; /* plain */ let y = 2; /**/ print y; /** Runs if x */ else { /** Inner */ print x; }",
+    ).unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    let doc = |fragment: &Fragment| fragment.get_doc_comment().map(|token| token.token_type.clone().into_owned());
+
+    // The doc comment isn't part of the statement's tokens
+    test_statement(&composed[0], vec![
+        Keyword("let".into()),
+        Identifier("x".into()),
+        Operator("=".into()),
+        Literal(LiteralKind::Integer, "1".into()),
+    ]);
+    assert_eq!(Some(DocComment(" Declares x ".into())), doc(&composed[0]));
+
+    // Ordinary and empty docblocks are ignored
+    assert_eq!(None, doc(&composed[1]));
+    assert_eq!(None, doc(&composed[2]));
+
+    // Single tokens leave the doc comment to the following encapsulation
+    assert_eq!(None, doc(&composed[3]));
+    assert_eq!(Some(DocComment(" Runs if x ".into())), doc(&composed[4]));
+    assert_eq!(Some(DocComment(" Inner ".into())), doc(&composed[4].get_fragments().unwrap()[0]));
+
+    // A doc comment in the middle of a statement doesn't split it
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "let x = /** doc */ 1; print x;").unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+    assert_eq!(2, composed.len());
+    test_statement(&composed[0], vec![
+        Keyword("let".into()),
+        Identifier("x".into()),
+        Operator("=".into()),
+        DocComment(" doc ".into()),
+        Literal(LiteralKind::Integer, "1".into()),
+    ]);
+    assert_eq!(None, doc(&composed[0]));
+    assert_eq!(None, doc(&composed[1]));
+
+    // The statement terminator ends what a doc comment can document
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "/** Doc for x */ x; let y = 1; /** d */ ; let z = 2;").unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+    assert_eq!(3, composed.len());
+    assert!(composed.iter().all(|fragment| doc(fragment).is_none()));
+
+    // When newlines end statements, the doc comment can be on a line of its own
+    let langdef: LanguageDefinition = default_langdef().with_statement_terminator(Newline);
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "/** Doc for y */\nlet y = 1\n").unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+    assert_eq!(Some(DocComment(" Doc for y ".into())), doc(&composed[0]));
+}
+
+#[test]
+fn composer_indentation() {
+    let langdef: LanguageDefinition = default_langdef()
+        .with_statement_terminator(Newline)
+        .with_significant_indentation(true);
+    let stream: TokenStream = Tokenizer::tokenize(
+        &langdef,
+        "if x:\n    let y = 1\n    if y:\n        print y\nprint x\n",
+    ).unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    assert_eq!(3, composed.len());
+    test_statement(&composed[0], vec![
+        Keyword("if".into()),
+        Identifier("x".into()),
+        Punctuator(':'),
+    ]);
+    test_statement(&composed[2], vec![
+        Identifier("print".into()),
+        Identifier("x".into()),
+    ]);
+
+    // The indented lines form a block, like curly brackets would
+    let block: &Composition = composed[1].get_fragments().unwrap();
+    assert_eq!(Some(ComposerContext::Closure(BracketType::Curly)), composed[1].get_context());
+    assert_eq!(3, block.len());
+    test_statement(&block[1], vec![
+        Keyword("if".into()),
+        Identifier("y".into()),
+        Punctuator(':'),
+    ]);
+    test_statement(&block[2].get_fragments().unwrap()[0], vec![
+        Identifier("print".into()),
+        Identifier("y".into()),
+    ]);
+
+    // With another terminator, the line breaks are left out
+    let langdef: LanguageDefinition = default_langdef().with_significant_indentation(true);
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "if x:\n    a;\nb;\n").unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    assert_eq!(3, composed.len());
+    test_statement(&composed[0], vec![
+        Keyword("if".into()),
+        Identifier("x".into()),
+        Punctuator(':'),
+    ]);
+    assert_eq!(3, composed[0].get_tokens().len());
+    assert_eq!(1, composed[1].get_fragments().unwrap().len());
+    assert_eq!(Some(&Identifier("a".into())), composed[1].get_fragments().unwrap()[0].get_token().map(|token| &token.token_type));
+    assert_eq!(Some(&Identifier("b".into())), composed[2].get_token().map(|token| &token.token_type));
+
+    // Line breaks end the statement along with the terminator
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "x = 1\ny = 2;\n").unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+    assert_eq!(2, composed.len());
+    assert!(composed.iter().all(|fragment| fragment.is_terminated()));
+    assert_eq!(3, composed[0].get_tokens().len());
+}
+
+#[test]
+fn composer_automatic_termination() {
+    let langdef: LanguageDefinition = default_langdef()
+        .with_statement_terminator(Newline)
+        .with_line_continuation(Some('\\'));
+    let stream: TokenStream = Tokenizer::tokenize(
+        &langdef,
+        "let x = 1 +\n    2\nlet y = x \\\n    * 3\nprint [x,\n    y]\nif x {\n    print x\n    print y\n}\n",
+    ).unwrap();
+    let composed: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    // Lines ending with a binary operator or the line continuation continue on the next
+    test_statement(&composed[0], vec![
+        Keyword("let".into()),
+        Identifier("x".into()),
+        Operator("=".into()),
+        Literal(LiteralKind::Integer, "1".into()),
+        Operator("+".into()),
+        Literal(LiteralKind::Integer, "2".into()),
+    ]);
+    test_statement(&composed[1], vec![
+        Keyword("let".into()),
+        Identifier("y".into()),
+        Operator("=".into()),
+        Identifier("x".into()),
+        Operator("*".into()),
+        Literal(LiteralKind::Integer, "3".into()),
+    ]);
+
+    // Line breaks inside square brackets are ignored
+    test_statement(&composed[3].get_fragments().unwrap()[0], vec![
+        Identifier("x".into()),
+        Punctuator(','),
+        Identifier("y".into()),
+    ]);
+
+    // Inside a block, every line is a statement
+    let block: &Composition = composed[5].get_fragments().unwrap();
+    assert_eq!(2, block.len());
+    test_statement(&block[1], vec![
+        Identifier("print".into()),
+        Identifier("y".into()),
+    ]);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use rust_lexical_analyzer::{
+    tokenizer::{
+        Tokenizer,
+        TokenStream,
+        LiteralKind,
+        TokenType::*,
+    },
+    langdef::{
+        Associativity::*,
+        LanguageDefinition,
+    },
+    composer::{
+        Composer,
+        Composition,
+    },
+    expression::{
+        Expression,
+        ExpressionError,
+        ExpressionParser,
+    },
+    span::{Position, Span},
+};
+
+use common::*;
+
+/// Parses the document, and returns the expression in prefix notation
+fn parse(langdef: &LanguageDefinition, document: &str) -> Result<String, ExpressionError> {
+    let stream: TokenStream = Tokenizer::tokenize(langdef, document).unwrap();
+    let composition: Composition = Composer::compose(langdef, &stream).unwrap();
+    ExpressionParser::parse(langdef, &composition).map(|expression| expression.to_string())
+}
+
+#[test]
+fn precedence_and_associativity() {
+    let langdef: LanguageDefinition = default_langdef();
+    let expressions = vec![
+        ("30 + 18", "(+ 30 18)"),
+        ("1 + 2 * 3", "(+ 1 (* 2 3))"),
+        ("1 * 2 + 3", "(+ (* 1 2) 3)"),
+        ("10 - 4 - 3", "(- (- 10 4) 3)"),
+        ("2 ** 3 ** 2", "(** 2 (** 3 2))"),
+        ("-2 ** 2", "(- (** 2 2))"),
+        ("x = y = 1 + 2", "(= x (= y (+ 1 2)))"),
+        ("a || b && !c == d", "(|| a (&& b (== (! c) d)))"),
+        ("(1 + 2) * 3", "(* (+ 1 2) 3)"),
+        ("1 + ((2 - 3) * 4) / 5", "(+ 1 (/ (* (- 2 3) 4) 5))"),
+    ];
+
+    for (document, expected) in expressions {
+        assert_eq!(expected, parse(&langdef, document).unwrap(), "{}", document);
+    }
+}
+
+#[test]
+fn custom_operator_tables() {
+    let langdef: LanguageDefinition = default_langdef()
+        .with_operators(vec!["+", "*", "-", "!", "^"])
+        .with_prefix_operators(vec![("-", 5)])
+        .with_infix_operators(vec![("+", 1, Left), ("*", 2, Left), ("^", 3, Left)])
+        .with_postfix_operators(vec![("!", 7)]);
+
+    assert_eq!("(+ (- (! 3)) (* 2 (! x)))", parse(&langdef, "-3! + 2 * x!").unwrap());
+    assert_eq!("(^ (^ 2 3) 4)", parse(&langdef, "2 ^ 3 ^ 4").unwrap());
+}
+
+#[test]
+fn expression_structure() {
+    let langdef: LanguageDefinition = default_langdef();
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "(1 + 2) * x").unwrap();
+    let composition: Composition = Composer::compose(&langdef, &stream).unwrap();
+    let expression: Expression = ExpressionParser::parse(&langdef, &composition).unwrap();
+
+    match &expression {
+        Expression::Infix { operator, left, right } => {
+            assert_eq!(Operator("*".into()), operator.token_type);
+            assert!(matches!(**left, Expression::Group { .. }));
+            assert!(matches!(**right, Expression::Identifier(_)));
+        },
+        _ => panic!("Expected an infix expression: {:?}", expression),
+    }
+
+    assert_eq!(Span::new(Position::new(0, 1, 1), Position::new(11, 1, 12)), expression.get_span());
+
+    // Parsing an encapsulation builds the expression from its contents
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "if (a == 1) {}").unwrap();
+    let composition: Composition = Composer::compose(&langdef, &stream).unwrap();
+    assert_eq!("(== a 1)", ExpressionParser::parse_fragment(&langdef, &composition[1]).unwrap().to_string());
+}
+
+#[test]
+fn expression_errors() {
+    let langdef: LanguageDefinition = default_langdef();
+
+    assert!(matches!(
+        parse(&langdef, "1 +").unwrap_err(),
+        ExpressionError::UnexpectedEnd { span } if span.start.offset == 2,
+    ));
+
+    assert!(matches!(
+        parse(&langdef, "1 2").unwrap_err(),
+        ExpressionError::UnexpectedToken { token } if token.token_type == Literal(LiteralKind::Integer, "2".into()),
+    ));
+
+    assert!(matches!(
+        parse(&langdef, "* 2").unwrap_err(),
+        ExpressionError::UnexpectedToken { token } if token.token_type == Operator("*".into()),
+    ));
+
+    assert!(matches!(
+        parse(&langdef, "1 + [2]").unwrap_err(),
+        ExpressionError::UnexpectedBlock { .. },
+    ));
+
+    assert_eq!(
+        "Expression ends unexpectedly at 1:2",
+        parse(&langdef, "()").unwrap_err().to_string(),
+    );
+    assert_eq!(
+        "Expression ends unexpectedly at 1:6",
+        parse(&langdef, "1 + ()").unwrap_err().to_string(),
+    );
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::{cell::RefCell, collections::HashMap};
+use rust_lexical_analyzer::{
+    tokenizer::{
+        Token,
+        Tokenizer,
+        TokenStream,
+        TokenType::{self, *},
+    },
+    langdef::LanguageDefinition,
+    composer::{
+        BracketType::*,
+        Composer,
+        Composition,
+    },
+    grammar::{
+        Grammar,
+        GrammarError,
+        Pattern::*,
+    },
+    expression::ExpressionParser,
+};
+
+use common::*;
+
+fn tokenize<'src>(langdef: &LanguageDefinition, document: &'src str) -> TokenStream<'src> {
+    Tokenizer::tokenize(langdef, document).unwrap()
+}
+
+#[test]
+fn grammar_interpreter() {
+    let langdef: LanguageDefinition = default_langdef();
+    let document: String = get_test_file("grammar.txt");
+    let stream: TokenStream = tokenize(&langdef, &document);
+    let composition: Composition = Composer::compose(&langdef, &stream).unwrap();
+
+    let variables: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+    let output: RefCell<Vec<String>> = RefCell::new(vec![]);
+
+    let value_of = |token: &Token| match &token.token_type {
+        Identifier(name) => variables.borrow().get(name.as_ref()).cloned().unwrap_or_default(),
+        Literal(_, value) => value.to_string(),
+        _ => String::new(),
+    };
+
+    let grammar: Grammar<()> = Grammar::new()
+        .with_rule(
+            vec![Token(Keyword("let".into())), AnyIdentifier, Token(Operator("=".into())), AnyLiteral],
+            |_, matched| {
+                if let (Some(Identifier(name)), Some(Literal(_, value))) = (
+                    matched.token(1).map(|t| &t.token_type),
+                    matched.token(3).map(|t| &t.token_type),
+                ) {
+                    variables.borrow_mut().insert(name.to_string(), value.to_string());
+                }
+            },
+        )
+        .with_rule(
+            vec![
+                Token(Keyword("if".into())),
+                Expression,
+                Block(Some(Curly)),
+                Token(Keyword("else".into())),
+                Block(Some(Curly)),
+            ],
+            |grammar, matched| {
+                let condition: &[Token] = matched.tokens(1).unwrap();
+                let branch: usize = if value_of(&condition[0]) == value_of(&condition[2]) { 2 } else { 4 };
+                grammar.run(&matched.block(branch).unwrap().composition).unwrap();
+            },
+        )
+        .with_rule(
+            vec![Token(Identifier("print".into())), AnyToken],
+            |_, matched| output.borrow_mut().push(value_of(matched.token(1).unwrap())),
+        );
+
+    assert_eq!(4, grammar.run(&composition).unwrap().len());
+    assert_eq!(vec![String::from("30"), String::from("done")], *output.borrow());
+}
+
+#[test]
+fn grammar_rule_order_and_errors() {
+    let langdef: LanguageDefinition = default_langdef();
+    let grammar: Grammar<&str> = Grammar::new()
+        .with_rule(vec![Token(Keyword("let".into())), AnyIdentifier], |_, _| "declaration")
+        .with_rule(vec![Token(Keyword("let".into())), Expression], |_, _| "assignment")
+        .with_rule(vec![Block(Some(Square))], |_, _| "list")
+        .with_rule(vec![AnyToken], |_, _| "single");
+
+    let stream: TokenStream = tokenize(&langdef, "let x; let x = 1; [1, 2] else");
+    assert_eq!(
+        vec!["declaration", "assignment", "list", "single"],
+        grammar.run(&Composer::compose(&langdef, &stream).unwrap()).unwrap(),
+    );
+
+    let stream: TokenStream = tokenize(&langdef, "x = 1;");
+    let error: GrammarError = grammar.run(&Composer::compose(&langdef, &stream).unwrap()).unwrap_err();
+    assert_eq!("No grammar rule matches the code at 1:1", error.to_string());
+
+    // Rules must cover the entire statement
+    let grammar: Grammar<TokenType> = Grammar::new()
+        .with_rule(vec![AnyIdentifier], |_, matched| matched.token(0).unwrap().token_type.clone().into_owned());
+    let stream: TokenStream = tokenize(&langdef, "x y;");
+    assert!(grammar.run(&Composer::compose(&langdef, &stream).unwrap()).is_err());
+}
+
+#[test]
+fn grammar_expression_across_encapsulations() {
+    let langdef: LanguageDefinition = default_langdef();
+    let grammar: Grammar<String> = Grammar::new()
+        .with_rule(
+            vec![Token(Keyword("let".into())), AnyIdentifier, Token(Operator("=".into())), Expression],
+            |_, matched| {
+                let (tokens, fragments) = matched.expression(3).unwrap();
+                ExpressionParser::parse_continued(&langdef, tokens, fragments).unwrap().to_string()
+            },
+        )
+        .with_rule(
+            vec![Token(Identifier("print".into())), Expression],
+            |_, matched| format!("print {}", matched.tokens(1).unwrap().len()),
+        );
+
+    let stream: TokenStream = tokenize(&langdef, "let x = (1 + 2) * 3; print x; let y = 1 + (2); let z = /** doc */ (4);");
+    assert_eq!(
+        vec!["(* (+ 1 2) 3)", "print 1", "(+ 1 2)", "4"],
+        grammar.run(&Composer::compose(&langdef, &stream).unwrap()).unwrap(),
+    );
+}
+
+#[test]
+fn grammar_expression_stops_at_blocks() {
+    let langdef: LanguageDefinition = default_langdef();
+    let grammar: Grammar<&str> = Grammar::new()
+        .with_rule(vec![Token(Keyword("if".into())), Expression, Block(None)], |_, _| "if")
+        .with_rule(vec![Token(Keyword("else".into())), Block(None)], |_, _| "else");
+
+    // Consecutive blocks are matched one by one
+    let stream: TokenStream = tokenize(&langdef, "if a { x; } if b { y; } if c { z; }");
+    assert_eq!(vec!["if", "if", "if"], grammar.run(&Composer::compose(&langdef, &stream).unwrap()).unwrap());
+
+    // The condition doesn't reach past the block
+    let stream: TokenStream = tokenize(&langdef, "if x == 1 { a = 1; } else { a = 2; }");
+    assert_eq!(vec!["if", "else"], grammar.run(&Composer::compose(&langdef, &stream).unwrap()).unwrap());
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use rust_lexical_analyzer::{
+    tokenizer::{
+        Tokenizer,
+        TokenStream,
+        TokenType::*,
+    },
+    langdef::LanguageDefinition,
+    lexer::Lexer,
+    interner::{Interner, Symbol},
+};
+
+use common::*;
+
+#[test]
+fn interner() {
+    let mut interner: Interner = Interner::new();
+    let print: Symbol = interner.intern("print");
+    let x: Symbol = interner.intern("x");
+
+    assert_eq!(print, interner.intern("print"));
+    assert_ne!(print, x);
+    assert_eq!(2, interner.len());
+    assert_eq!(Some(x), interner.get("x"));
+    assert_eq!(None, interner.get("y"));
+    assert_eq!(Some("print"), interner.resolve(print));
+}
+
+#[test]
+fn keyword_symbols() {
+    let langdef: LanguageDefinition = default_langdef();
+    let mut interner: Interner = langdef.interner();
+
+    assert!(langdef.has_keyword("let"));
+    assert!(!langdef.has_keyword("print"));
+    assert!(langdef.is_keyword(interner.intern("else")));
+    assert!(!langdef.is_keyword(interner.intern("print")));
+}
+
+#[test]
+fn tokenize_interned() {
+    let langdef: LanguageDefinition = default_langdef();
+    let mut interner: Interner = langdef.interner();
+    let stream: TokenStream = Tokenizer::tokenize_interned(&langdef, "let x = 1; print x; if x {}", &mut interner).unwrap();
+
+    // Identical names share a symbol
+    assert_eq!(Identifier("x".into()), stream[1].token_type);
+    assert!(stream[1].symbol.is_some());
+    assert_eq!(stream[1].symbol, stream[6].symbol);
+    assert_eq!(stream[1].symbol, interner.get("x"));
+    assert_eq!(Keyword("if".into()), stream[8].token_type);
+    assert_eq!(stream[8].symbol, interner.get("if"));
+
+    // Only keywords and identifiers are interned
+    assert_eq!(None, stream[2].symbol);
+    assert_eq!(None, stream[3].symbol);
+
+    // The interner is kept between documents
+    let stream: TokenStream = Tokenizer::tokenize_interned(&langdef, "print y", &mut interner).unwrap();
+    assert_eq!(stream[0].symbol, interner.get("print"));
+    assert_eq!(Some("y"), interner.resolve(stream[1].symbol.unwrap()));
+
+    // Without an interner, tokens have no symbols
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, "let x").unwrap();
+    assert!(stream.iter().all(|token| token.symbol.is_none()));
+}
+
+#[test]
+fn lexer_with_interner() {
+    let langdef: LanguageDefinition = default_langdef();
+    let mut lexer: Lexer = Lexer::new(&langdef, "match value { else }").with_interner(Interner::new());
+    let stream: TokenStream = lexer.by_ref().collect::<Result<_, _>>().unwrap();
+
+    assert_eq!(Keyword("match".into()), stream[0].token_type);
+    assert_eq!(Keyword("else".into()), stream[3].token_type);
+
+    let interner: Interner = lexer.into_interner().unwrap();
+    assert_eq!(stream[1].symbol, interner.get("value"));
+    assert!(langdef.is_keyword(stream[0].symbol.unwrap()));
+    assert!(!langdef.is_keyword(stream[1].symbol.unwrap()));
+
+    // Keywords are recognized by their text, when the interner doesn't begin with them
+    let mut interner: Interner = Interner::new();
+    interner.intern("x");
+    assert!(!langdef.has_keyword_symbols(&interner));
+    assert!(langdef.has_keyword_symbols(&langdef.interner()));
+
+    let stream: TokenStream = Lexer::new(&langdef, "x y let").with_interner(interner).collect::<Result<_, _>>().unwrap();
+    assert_eq!(Identifier("x".into()), stream[0].token_type);
+    assert_eq!(Identifier("y".into()), stream[1].token_type);
+    assert_eq!(Keyword("let".into()), stream[2].token_type);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use std::{fs::File, io::{BufReader, ErrorKind, Read}};
+use rust_lexical_analyzer::{
+    lexer::Lexer,
+    tokenizer::{
+        Tokenizer,
+        TokenizeError,
+        LiteralKind,
+        Token,
+        TokenType::*,
+        TokenStream,
+    },
+    langdef::LanguageDefinition,
+};
+
+use common::*;
+
+/// A reader handing out a single byte at a time, which splits
+/// operators and multi-byte characters between chunks
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.split_first() {
+            Some((byte, rest)) if !buf.is_empty() => {
+                buf[0] = *byte;
+                self.0 = rest;
+                Ok(1)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn lexer_on_demand() {
+    let langdef: LanguageDefinition = default_langdef();
+
+    // Tokens are handed out before the error further ahead is found
+    let mut lexer: Lexer = Lexer::new(&langdef, "let x = \"unterminated");
+    assert_eq!(lexer.next().unwrap().unwrap().token_type, Keyword("let".into()));
+    assert_eq!(lexer.next().unwrap().unwrap().token_type, Identifier("x".into()));
+    assert_eq!(lexer.next().unwrap().unwrap().token_type, Operator("=".into()));
+    assert!(matches!(lexer.next(), Some(Err(TokenizeError::UnterminatedString { .. }))));
+    assert_eq!(lexer.next(), None);
+
+    let document: String = get_test_file("basic.txt");
+    let stream: TokenStream = Lexer::new(&langdef, &document).collect::<Result<_, _>>().unwrap();
+    assert_eq!(stream, Tokenizer::tokenize(&langdef, &document).unwrap());
+}
+
+#[test]
+fn lexer_from_reader() {
+    let langdef: LanguageDefinition = default_langdef();
+
+    for filename in ["basic.txt", "comments.txt", "composer_std.txt"] {
+        let document: String = get_test_file(filename);
+        let expected: TokenStream = Tokenizer::tokenize(&langdef, &document).unwrap();
+
+        let file: File = File::open(format!("./tests/res/{}", filename)).unwrap();
+        let stream: Result<TokenStream, TokenizeError> = Lexer::from_reader(&langdef, BufReader::new(file)).collect();
+        assert_eq!(stream.unwrap(), expected);
+    }
+
+    // Operators and characters split between chunks
+    let document: &str = "a <= \"ÆØÅ 😀\" >= 1.5e-3";
+    let stream: TokenStream = Lexer::from_reader(&langdef, Trickle(document.as_bytes()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    test_stream(
+        &stream,
+        vec![
+            Identifier("a".into()),
+            Operator("<=".into()),
+            Literal(LiteralKind::String, "ÆØÅ 😀".into()),
+            Operator(">=".into()),
+            Literal(LiteralKind::Float, "1.5e-3".into()),
+        ],
+    );
+    assert_eq!(stream, Tokenizer::tokenize(&langdef, document).unwrap());
+
+    // Trivia split between chunks
+    let document: String = get_test_file("comments.txt");
+    let stream: TokenStream = Lexer::from_reader(&langdef, Trickle(document.as_bytes()))
+        .with_trivia()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(stream, Tokenizer::tokenize_with_trivia(&langdef, &document).unwrap());
+
+    // Invalid UTF-8
+    let result: Vec<Result<Token, TokenizeError>> = Lexer::from_reader(&langdef, Trickle(b"ab \xFF cd")).collect();
+    assert_eq!(2, result.len());
+    assert_eq!(Ok(Identifier("ab".into())), result[0].clone().map(|token| token.token_type));
+    assert!(matches!(
+        result.last(),
+        Some(Err(TokenizeError::Io { kind: ErrorKind::InvalidData, message })) if message.ends_with("(at 1:4)"),
+    ));
+}
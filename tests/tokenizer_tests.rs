@@ -1,12 +1,23 @@
 mod common;
 
+use std::borrow::Cow;
 use rust_lexical_analyzer::{
     tokenizer::{
         Tokenizer,
-        TokenType::*,
+        TokenizeError,
+        LiteralKind,
+        TokenType::{self, *},
         TokenStream,
     },
-    langdef::LanguageDefinition,
+    langdef::{
+        IdentifierRules,
+        LanguageDefinition,
+        LexerMode,
+        WhitespaceRules,
+        NumberFormat,
+    },
+    span::{Position, Span},
+    interner::Interner,
 };
 
 use common::*;
@@ -14,28 +25,29 @@ use common::*;
 #[test]
 fn basic_syntax() {
     let langdef: LanguageDefinition = default_langdef();
-    let result: Result<TokenStream, ()> = Tokenizer::tokenize(
+    let document: String = get_test_file("basic.txt");
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
         &langdef,
-        get_test_file("basic.txt"),
+        &document,
     );
 
     test_stream(
         result.as_ref().unwrap(),
         vec![
-            Keyword(String::from("let")),
-            Identifier(String::from("name")),
-            Operator(String::from("=")),
-            Literal(String::from("John Doe")),
+            Keyword("let".into()),
+            Identifier("name".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::String, "John Doe".into()),
             Punctuator(';'),
-            Keyword(String::from("if")),
-            Identifier(String::from("variable")),
-            Operator(String::from("==")),
-            Literal(String::from("30")),
-            Operator(String::from("+")),
-            Literal(String::from("18")),
+            Keyword("if".into()),
+            Identifier("variable".into()),
+            Operator("==".into()),
+            Literal(LiteralKind::Integer, "30".into()),
+            Operator("+".into()),
+            Literal(LiteralKind::Integer, "18".into()),
             Punctuator('{'),
-            Identifier(String::from("print")),
-            Literal(String::from("Hello world")),
+            Identifier("print".into()),
+            Literal(LiteralKind::String, "Hello world".into()),
             Punctuator(';'),
             Punctuator('}'),
         ],
@@ -45,25 +57,25 @@ fn basic_syntax() {
 #[test]
 fn operators() {
     let langdef: LanguageDefinition = default_langdef();
-    let result: Result<TokenStream, ()> = Tokenizer::tokenize(
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
         &langdef,
-        "100 + 100 - 10 * 2 / 2 % 1".to_string(),
+        "100 + 100 - 10 * 2 / 2 % 1",
     );
 
     test_stream(
         result.as_ref().unwrap(),
         vec![
-            Literal(String::from("100")),
-            Operator(String::from("+")),
-            Literal(String::from("100")),
-            Operator(String::from("-")),
-            Literal(String::from("10")),
-            Operator(String::from("*")),
-            Literal(String::from("2")),
-            Operator(String::from("/")),
-            Literal(String::from("2")),
-            Operator(String::from("%")),
-            Literal(String::from("1")),
+            Literal(LiteralKind::Integer, "100".into()),
+            Operator("+".into()),
+            Literal(LiteralKind::Integer, "100".into()),
+            Operator("-".into()),
+            Literal(LiteralKind::Integer, "10".into()),
+            Operator("*".into()),
+            Literal(LiteralKind::Integer, "2".into()),
+            Operator("/".into()),
+            Literal(LiteralKind::Integer, "2".into()),
+            Operator("%".into()),
+            Literal(LiteralKind::Integer, "1".into()),
         ],
     );
 }
@@ -71,31 +83,737 @@ fn operators() {
 #[test]
 fn comments() {
     let langdef: LanguageDefinition = default_langdef();
-    let result: Result<TokenStream, ()> = Tokenizer::tokenize(
+    let document: String = get_test_file("comments.txt");
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
         &langdef,
-        get_test_file("comments.txt"),
+        &document,
     );
 
     test_stream(
         result.as_ref().unwrap(),
         vec![
-            Keyword(String::from("let")),
-            Identifier(String::from("below_docblock")),
-            Operator(String::from("=")),
-            Literal(String::from("1")),
+            DocComment("\n * A docblock to be ignored\n ".into()),
+            Keyword("let".into()),
+            Identifier("below_docblock".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+            Punctuator(';'),
+
+            Keyword("let".into()),
+            Identifier("x".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::Integer, "2".into()),
             Punctuator(';'),
 
-            Keyword(String::from("let")),
-            Identifier(String::from("x")),
-            Operator(String::from("=")),
-            Literal(String::from("2")),
+            Keyword("let".into()),
+            Identifier("x".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::Integer, "3".into()),
             Punctuator(';'),
+        ],
+    );
+}
+
+#[test]
+fn spans() {
+    let langdef: LanguageDefinition = default_langdef();
+    let document: String = get_test_file("basic.txt");
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        &document,
+    );
+    let stream: TokenStream = result.unwrap();
+
+    // let
+    assert_eq!(Span::new(Position::new(0, 1, 1), Position::new(3, 1, 4)), stream[0].span);
+
+    // "John Doe" (including the quotation marks)
+    assert_eq!(Span::new(Position::new(11, 1, 12), Position::new(21, 1, 22)), stream[3].span);
+
+    // ;
+    assert_eq!(Span::new(Position::new(21, 1, 22), Position::new(22, 1, 23)), stream[4].span);
+
+    // print (on the fourth line, indented by four spaces)
+    assert_eq!(Span::new(Position::new(53, 4, 5), Position::new(58, 4, 10)), stream[12].span);
+}
+
+#[test]
+fn errors() {
+    let langdef: LanguageDefinition = default_langdef();
+
+    assert_eq!(
+        Err(TokenizeError::UnterminatedString { start: Position::new(8, 1, 9) }),
+        Tokenizer::tokenize(&langdef, "let x = \"Hello;"),
+    );
+
+    assert_eq!(
+        Err(TokenizeError::UnterminatedDocBlock { start: Position::new(1, 2, 1) }),
+        Tokenizer::tokenize(&langdef, "\n/* let x = 1;"),
+    );
+
+    assert_eq!(
+        Err(TokenizeError::InvalidCharacter { character: '\u{7}', position: Position::new(2, 1, 3) }),
+        Tokenizer::tokenize(&langdef, "x \u{7};"),
+    );
+
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, "1.2.3");
+    assert_eq!(
+        Err(TokenizeError::MalformedNumber {
+            value: String::from("1.2.3"),
+            span: Span::new(Position::new(0, 1, 1), Position::new(5, 1, 6)),
+        }),
+        result,
+    );
+    assert_eq!("Malformed number \"1.2.3\" at 1:1", result.unwrap_err().to_string());
+}
+
+#[test]
+fn escape_sequences() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        r#"print "say \"hi\"" "a\\b\n\t\r\0" "\u{1F600}\x41";"#,
+    );
 
-            Keyword(String::from("let")),
-            Identifier(String::from("x")),
-            Operator(String::from("=")),
-            Literal(String::from("3")),
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("print".into()),
+            Literal(LiteralKind::String, "say \"hi\"".into()),
+            Literal(LiteralKind::String, "a\\b\n\t\r\0".into()),
+            Literal(LiteralKind::String, "\u{1F600}A".into()),
             Punctuator(';'),
         ],
     );
+
+    assert_eq!(
+        Err(TokenizeError::InvalidEscape { sequence: String::from("\\q"), position: Position::new(3, 1, 4) }),
+        Tokenizer::tokenize(&langdef, r#"x "\q";"#),
+    );
+
+    assert_eq!(
+        Err(TokenizeError::InvalidEscape { sequence: String::from("\\u{12G"), position: Position::new(1, 1, 2) }),
+        Tokenizer::tokenize(&langdef, r#""\u{12G}""#),
+    );
+
+    // The closing quote ends an unfinished escape sequence
+    assert_eq!(
+        Err(TokenizeError::InvalidEscape { sequence: String::from("\\x4"), position: Position::new(1, 1, 2) }),
+        Tokenizer::tokenize(&langdef, r#""\x4""#),
+    );
+
+    // Without escape sequences, backslashes are kept as they are
+    let langdef: LanguageDefinition = default_langdef().with_escapes(vec![]);
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, r#""C:\dir\""#);
+    test_stream(result.as_ref().unwrap(), vec![Literal(LiteralKind::String, "C:\\dir\\".into())]);
+}
+
+#[test]
+fn multi_character_operators() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "a<=b != c >= d && e || f -> g => h += i :: j ** k",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("a".into()),
+            Operator("<=".into()),
+            Identifier("b".into()),
+            Operator("!=".into()),
+            Identifier("c".into()),
+            Operator(">=".into()),
+            Identifier("d".into()),
+            Operator("&&".into()),
+            Identifier("e".into()),
+            Operator("||".into()),
+            Identifier("f".into()),
+            Operator("->".into()),
+            Identifier("g".into()),
+            Operator("=>".into()),
+            Identifier("h".into()),
+            Operator("+=".into()),
+            Identifier("i".into()),
+            Operator("::".into()),
+            Identifier("j".into()),
+            Operator("**".into()),
+            Identifier("k".into()),
+        ],
+    );
+    assert_eq!(Span::new(Position::new(1, 1, 2), Position::new(3, 1, 4)), result.unwrap()[1].span);
+
+    // Only the operators declared in the language definition are recognized
+    let langdef: LanguageDefinition = default_langdef().with_operators(vec!["<", "<<"]);
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, "a<<<b");
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("a".into()),
+            Operator("<<".into()),
+            Operator("<".into()),
+            Identifier("b".into()),
+        ],
+    );
+}
+
+#[test]
+fn punctuators() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "f(a,b); obj.field ? x : 1.5;",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("f".into()),
+            Punctuator('('),
+            Identifier("a".into()),
+            Punctuator(','),
+            Identifier("b".into()),
+            Punctuator(')'),
+            Punctuator(';'),
+            Identifier("obj".into()),
+            Punctuator('.'),
+            Identifier("field".into()),
+            Punctuator('?'),
+            Identifier("x".into()),
+            Punctuator(':'),
+            Literal(LiteralKind::Float, "1.5".into()),
+            Punctuator(';'),
+        ],
+    );
+
+    // Characters which aren't declared as punctuators can become part of identifiers
+    let langdef: LanguageDefinition = default_langdef()
+        .with_punctuators(vec![';', '$'])
+        .with_identifiers(IdentifierRules::Custom {
+            start: |c| c.is_alphabetic(),
+            continues: |c| c.is_alphanumeric() || c == '.',
+        });
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, "a.b$c;");
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("a.b".into()),
+            Punctuator('$'),
+            Identifier("c".into()),
+            Punctuator(';'),
+        ],
+    );
+}
+
+#[test]
+fn numeric_literals() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "0xFF 0b1010 0o17 1e-9 2.5E+3 1_000_000 .5 x-1e3 obj.5",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Literal(LiteralKind::Integer, "0xFF".into()),
+            Literal(LiteralKind::Integer, "0b1010".into()),
+            Literal(LiteralKind::Integer, "0o17".into()),
+            Literal(LiteralKind::Float, "1e-9".into()),
+            Literal(LiteralKind::Float, "2.5E+3".into()),
+            Literal(LiteralKind::Integer, "1_000_000".into()),
+            Literal(LiteralKind::Float, ".5".into()),
+            Identifier("x".into()),
+            Operator("-".into()),
+            Literal(LiteralKind::Float, "1e3".into()),
+            Identifier("obj".into()),
+            Punctuator('.'),
+            Literal(LiteralKind::Integer, "5".into()),
+        ],
+    );
+
+    for malformed in ["0x", "1.2.3", "0b102", "1e", "1__0_", "12abc"] {
+        assert!(
+            matches!(
+                Tokenizer::tokenize(&langdef, malformed),
+                Err(TokenizeError::MalformedNumber { value, .. }) if value == malformed,
+            ),
+            "{}",
+            malformed,
+        );
+    }
+
+    // Disabled notations are no longer recognized as numbers
+    let langdef: LanguageDefinition = default_langdef().with_number_formats(vec![NumberFormat::Binary]);
+    assert!(Tokenizer::tokenize(&langdef, "0xFF").is_err());
+    assert!(Tokenizer::tokenize(&langdef, "1_000").is_err());
+    assert!(Tokenizer::tokenize(&langdef, "1e-9").is_err());
+    test_stream(
+        &Tokenizer::tokenize(&langdef, "0b11 .5").unwrap(),
+        vec![Literal(LiteralKind::Integer, "0b11".into()), Punctuator('.'), Literal(LiteralKind::Integer, "5".into())],
+    );
+}
+
+#[test]
+fn literal_kinds() {
+    let langdef: LanguageDefinition = default_langdef();
+    let stream: TokenStream = Tokenizer::tokenize(
+        &langdef,
+        "\"30\" 30 0xFF 1_000 2.5 1e3 true false null 'a' '\\''",
+    ).unwrap();
+
+    test_stream(
+        &stream,
+        vec![
+            Literal(LiteralKind::String, "30".into()),
+            Literal(LiteralKind::Integer, "30".into()),
+            Literal(LiteralKind::Integer, "0xFF".into()),
+            Literal(LiteralKind::Integer, "1_000".into()),
+            Literal(LiteralKind::Float, "2.5".into()),
+            Literal(LiteralKind::Float, "1e3".into()),
+            Literal(LiteralKind::Bool, "true".into()),
+            Literal(LiteralKind::Bool, "false".into()),
+            Literal(LiteralKind::Null, "null".into()),
+            Literal(LiteralKind::Char, "a".into()),
+            Literal(LiteralKind::Char, "'".into()),
+        ],
+    );
+
+    assert_eq!(stream[0].as_integer(), None);
+    assert_eq!(stream[1].as_integer(), Some(30));
+    assert_eq!(stream[2].as_integer(), Some(255));
+    assert_eq!(stream[3].as_integer(), Some(1000));
+    assert_eq!(stream[3].as_float(), Some(1000.0));
+    assert_eq!(stream[4].as_float(), Some(2.5));
+    assert_eq!(stream[5].as_float(), Some(1000.0));
+    assert_eq!(stream[5].as_integer(), None);
+    assert_eq!(stream[6].as_bool(), Some(true));
+    assert_eq!(stream[7].as_bool(), Some(false));
+    assert_eq!(stream[8].literal_kind(), Some(LiteralKind::Null));
+    assert_eq!(stream[9].as_char(), Some('a'));
+
+    assert!(matches!(
+        Tokenizer::tokenize(&langdef, "'ab'"),
+        Err(TokenizeError::MalformedCharacter { value, .. }) if value == "ab",
+    ));
+    assert!(matches!(
+        Tokenizer::tokenize(&langdef, "'a"),
+        Err(TokenizeError::UnterminatedString { .. }),
+    ));
+
+    // Without a char delimiter, the quote has no place in the language
+    let langdef: LanguageDefinition = default_langdef().with_char_delimiter(None);
+    assert_eq!(
+        TokenizeError::InvalidCharacter { character: '\'', position: Position::new(3, 1, 4) },
+        Tokenizer::tokenize(&langdef, "don't").unwrap_err(),
+    );
+}
+
+#[test]
+fn borrowed_tokens() {
+    let langdef: LanguageDefinition = default_langdef();
+    let document: &str = "let name = \"John\" + \"Doe\\n\";";
+    let stream: TokenStream = Tokenizer::tokenize(&langdef, document).unwrap();
+
+    let is_borrowed = |token_type: &TokenType| matches!(
+        token_type,
+        Keyword(Cow::Borrowed(_))
+            | Identifier(Cow::Borrowed(_))
+            | Operator(Cow::Borrowed(_))
+            | Literal(_, Cow::Borrowed(_))
+    );
+
+    // Only the string with an escape sequence differs from the document
+    assert!(is_borrowed(&stream[0].token_type));
+    assert!(is_borrowed(&stream[1].token_type));
+    assert!(is_borrowed(&stream[2].token_type));
+    assert!(is_borrowed(&stream[3].token_type));
+    assert!(!is_borrowed(&stream[5].token_type));
+    assert_eq!(Literal(LiteralKind::String, "Doe\n".into()), stream[5].token_type);
+
+    // The text is a slice of the document itself
+    match &stream[1].token_type {
+        Identifier(Cow::Borrowed(name)) => assert!(std::ptr::eq(*name, &document[4..8])),
+        other => panic!("Expected a borrowed identifier: {:?}", other),
+    }
+
+    let owned: TokenStream<'static> = stream.into_iter().map(|token| token.into_owned()).collect();
+    assert!(!is_borrowed(&owned[1].token_type));
+    assert_eq!(Identifier("name".into()), owned[1].token_type);
+}
+
+#[test]
+fn trivia() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize_with_trivia(
+        &langdef,
+        "let x = 1;  // one\r\n/* two */ # three",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Keyword("let".into()),
+            Whitespace(" ".into()),
+            Identifier("x".into()),
+            Whitespace(" ".into()),
+            Operator("=".into()),
+            Whitespace(" ".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+            Punctuator(';'),
+            Whitespace("  ".into()),
+            LineComment("// one".into()),
+            Whitespace("\r".into()),
+            Newline,
+            BlockComment("/* two */".into()),
+            Whitespace(" ".into()),
+            LineComment("# three".into()),
+        ],
+    );
+}
+
+#[test]
+fn trivia_is_lossless() {
+    let langdef: LanguageDefinition = default_langdef();
+    let document: String = get_test_file("comments.txt");
+    let stream: TokenStream = Tokenizer::tokenize_with_trivia(&langdef, &document).unwrap();
+
+    // The spans of the tokens cover the entire document
+    let reproduced: String = stream
+        .iter()
+        .map(|token| &document[token.span.start.offset..token.span.end.offset])
+        .collect();
+    assert_eq!(document, reproduced);
+
+    // Leaving out the trivia gives the ordinary token stream
+    let tokens: Vec<TokenType> = stream
+        .into_iter()
+        .filter(|token| !token.token_type.is_trivia() && token.token_type != Newline)
+        .map(|token| token.token_type)
+        .collect();
+    let expected: Vec<TokenType> = Tokenizer::tokenize(&langdef, &document)
+        .unwrap()
+        .into_iter()
+        .map(|token| token.token_type)
+        .collect();
+    assert_eq!(expected, tokens);
+
+    // The line continuation is kept as well
+    let langdef: LanguageDefinition = default_langdef()
+        .with_statement_terminator(Newline)
+        .with_line_continuation(Some('\\'));
+    let document: &str = "a = 1 \\\n + 2\n";
+    let stream: TokenStream = Tokenizer::tokenize_with_trivia(&langdef, document).unwrap();
+    let reproduced: String = stream
+        .iter()
+        .map(|token| &document[token.span.start.offset..token.span.end.offset])
+        .collect();
+    assert_eq!(document, reproduced);
+    assert!(stream.iter().any(|token| token.token_type == LineContinuation('\\')));
+    assert_eq!(1, Tokenizer::tokenize(&langdef, document).unwrap().iter().filter(|token| token.token_type == Newline).count());
+
+    // Only whitespace may follow the line continuation on the line
+    assert_eq!(
+        TokenizeError::InvalidCharacter { character: '\\', position: Position::new(6, 1, 7) },
+        Tokenizer::tokenize(&langdef, "a = 1 \\ + 2\n").unwrap_err(),
+    );
+    assert!(Tokenizer::tokenize(&langdef, "a = 1 \\  \n + 2\n").is_ok());
+}
+
+#[test]
+fn custom_comments() {
+    let langdef: LanguageDefinition = default_langdef()
+        .with_operators(vec!["=", "-", "#"])
+        .with_punctuators(vec!['(', ')'])
+        .with_line_comments(vec!["--", ";"])
+        .with_block_comments(vec![("{-", "-}"), ("(*", "*)")]);
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "let x = #y -- comment\n{- block // -} x - 1 ; comment\n(** doc *) (x)",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Keyword("let".into()),
+            Identifier("x".into()),
+            Operator("=".into()),
+            Operator("#".into()),
+            Identifier("y".into()),
+            Identifier("x".into()),
+            Operator("-".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+            DocComment(" doc ".into()),
+            Punctuator('('),
+            Identifier("x".into()),
+            Punctuator(')'),
+        ],
+    );
+
+    // Without nesting, the first closer ends the comment
+    let document: &str = "/* outer /* inner */ x */";
+    let stream: TokenStream = Tokenizer::tokenize(&default_langdef(), document).unwrap();
+    assert_eq!(Identifier("x".into()), stream[0].token_type);
+
+    let langdef: LanguageDefinition = default_langdef().with_nested_comments(true);
+    assert!(Tokenizer::tokenize(&langdef, document).unwrap().is_empty());
+}
+
+#[test]
+fn nested_comments() {
+    let langdef: LanguageDefinition = default_langdef().with_nested_comments(true);
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "/* outer /* inner */ still comment */ x /** doc /* nested */ */ y",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("x".into()),
+            DocComment(" doc /* nested */ ".into()),
+            Identifier("y".into()),
+        ],
+    );
+
+    // The innermost comment which is still open is reported
+    assert_eq!(
+        TokenizeError::UnterminatedDocBlock { start: Position::new(13, 2, 9) },
+        Tokenizer::tokenize(&langdef, "/* a\n/* b */ /* c").unwrap_err(),
+    );
+    assert_eq!(
+        TokenizeError::UnterminatedDocBlock { start: Position::new(2, 1, 3) },
+        Tokenizer::tokenize(&langdef, "x /* a /* b */").unwrap_err(),
+    );
+
+    // Trivia covers the entire nested comment
+    let document: &str = "/* a /* b */ c */ x";
+    let stream: TokenStream = Tokenizer::tokenize_with_trivia(&langdef, document).unwrap();
+    assert_eq!(BlockComment("/* a /* b */ c */".into()), stream[0].token_type);
+}
+
+#[test]
+fn identifiers() {
+    let langdef: LanguageDefinition = default_langdef();
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "_private\tnaïve = größe + 変数2;",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("_private".into()),
+            Identifier("naïve".into()),
+            Operator("=".into()),
+            Identifier("größe".into()),
+            Operator("+".into()),
+            Identifier("変数2".into()),
+            Punctuator(';'),
+        ],
+    );
+
+    // Stray characters are reported with their position
+    let invalid = |document: &str| match Tokenizer::tokenize(&langdef, document) {
+        Err(TokenizeError::InvalidCharacter { character, position }) => Some((character, position.offset)),
+        _ => None,
+    };
+    assert_eq!(Some(('@', 4)), invalid("let @x = 1;"));
+    assert_eq!(Some(('$', 5)), invalid("price$ = 1;"));
+    assert_eq!(Some(('😀', 4)), invalid("let 😀 = 1;"));
+    assert_eq!(Some(('\u{7}', 1)), invalid("a\u{7}"));
+
+    // Custom rules, where identifiers can begin with $ and contain @
+    let langdef: LanguageDefinition = default_langdef().with_identifiers(IdentifierRules::Custom {
+        start: |c| c == '$' || c.is_ascii_alphabetic(),
+        continues: |c| c == '@' || c.is_ascii_alphanumeric(),
+    });
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, "$user@host - 1");
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("$user@host".into()),
+            Operator("-".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+        ],
+    );
+}
+
+#[test]
+fn whitespace() {
+    let langdef: LanguageDefinition = default_langdef();
+    let document: &str = "let\tx\u{a0}=\u{2003}1;\n\tlet\u{c}y = 2;";
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, document);
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Keyword("let".into()),
+            Identifier("x".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+            Punctuator(';'),
+            Keyword("let".into()),
+            Identifier("y".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::Integer, "2".into()),
+            Punctuator(';'),
+        ],
+    );
+
+    let stream: TokenStream = Tokenizer::tokenize_with_trivia(&langdef, "a \t\u{a0}b").unwrap();
+    assert_eq!(Whitespace(" \t\u{a0}".into()), stream[1].token_type);
+
+    // Custom rules, where only spaces and tabs separate tokens
+    let langdef: LanguageDefinition = default_langdef().with_whitespace(WhitespaceRules::Custom(|c| c == ' ' || c == '\t'));
+    assert_eq!(
+        TokenizeError::InvalidCharacter { character: '\u{a0}', position: Position::new(3, 1, 4) },
+        Tokenizer::tokenize(&langdef, "let\u{a0}x\n").unwrap_err(),
+    );
+}
+
+#[test]
+fn indentation() {
+    let langdef: LanguageDefinition = default_langdef().with_significant_indentation(true);
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(
+        &langdef,
+        "if x:\n    let y = f(1,\n  2)\n\n    # comment\n    if y:\n        print y\nprint x",
+    );
+
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Keyword("if".into()),
+            Identifier("x".into()),
+            Punctuator(':'),
+            Newline,
+            Indent,
+            Keyword("let".into()),
+            Identifier("y".into()),
+            Operator("=".into()),
+            Identifier("f".into()),
+            Punctuator('('),
+            Literal(LiteralKind::Integer, "1".into()),
+            Punctuator(','),
+            Literal(LiteralKind::Integer, "2".into()),
+            Punctuator(')'),
+            Newline,
+            Newline,
+            Newline,
+            Keyword("if".into()),
+            Identifier("y".into()),
+            Punctuator(':'),
+            Newline,
+            Indent,
+            Identifier("print".into()),
+            Identifier("y".into()),
+            Newline,
+            Dedent,
+            Dedent,
+            Identifier("print".into()),
+            Identifier("x".into()),
+        ],
+    );
+
+    // Levels still open are closed at the end of the document
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, "a\n\tb\n\t\tc");
+    test_stream(
+        result.as_ref().unwrap(),
+        vec![
+            Identifier("a".into()),
+            Newline,
+            Indent,
+            Identifier("b".into()),
+            Newline,
+            Indent,
+            Identifier("c".into()),
+            Dedent,
+            Dedent,
+        ],
+    );
+
+    // Lines holding nothing but comments don't change the indentation
+    for document in ["if x:\n    a\n/* note */\n    b", "if x:\n    a\n  /** doc */\n    b"] {
+        let stream: TokenStream = Tokenizer::tokenize(&langdef, document).unwrap();
+        assert_eq!(1, stream.iter().filter(|token| token.token_type == Indent).count());
+        assert_eq!(1, stream.iter().filter(|token| token.token_type == Dedent).count());
+        assert_eq!(Some(&Dedent), stream.last().map(|token| &token.token_type));
+    }
+
+    assert_eq!(
+        TokenizeError::InconsistentDedent { position: Position::new(14, 3, 3) },
+        Tokenizer::tokenize(&langdef, "if x:\n    a\n  b").unwrap_err(),
+    );
+    assert_eq!(
+        TokenizeError::MixedIndentation { position: Position::new(7, 2, 2) },
+        Tokenizer::tokenize(&langdef, "if x:\n \tb").unwrap_err(),
+    );
+    assert_eq!(
+        TokenizeError::MixedIndentation { position: Position::new(10, 5, 1) },
+        Tokenizer::tokenize(&langdef, "a:\n\tb\n\nc:\n    d").unwrap_err(),
+    );
+}
+
+#[test]
+fn lexer_modes() {
+    let sql: LanguageDefinition = LanguageDefinition::new(vec!["SELECT", "FROM", "WHERE"])
+        .with_operators(vec!["=", "*"])
+        .with_line_comments(vec!["--"]);
+    let langdef: LanguageDefinition = LanguageDefinition::new(vec!["let", "sql"])
+        .with_operators(vec!["=", "-", "~/"])
+        .with_modes(vec![
+            LexerMode::Language { name: "sql", opener: Keyword("sql".into()), language: Box::new(sql), closer: Punctuator('}') },
+            LexerMode::Raw { name: "regex", opener: Operator("~/".into()), closer: "/" },
+        ]);
+    let document: &str = "let rows = sql{ SELECT * FROM t -- all\n WHERE id = 1 } - 1; let r = ~/a+\\/b/;";
+    let result: Result<TokenStream, TokenizeError> = Tokenizer::tokenize(&langdef, document);
+
+    let stream: &TokenStream = result.as_ref().unwrap();
+    assert_eq!(23, stream.len());
+    test_stream(
+        stream,
+        vec![
+            Keyword("let".into()),
+            Identifier("rows".into()),
+            Operator("=".into()),
+            Keyword("sql".into()),
+
+            // The rules of SQL apply until the closing bracket
+            Punctuator('{'),
+            Keyword("SELECT".into()),
+            Operator("*".into()),
+            Keyword("FROM".into()),
+            Identifier("t".into()),
+            Keyword("WHERE".into()),
+            Identifier("id".into()),
+            Operator("=".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+            Punctuator('}'),
+            Operator("-".into()),
+            Literal(LiteralKind::Integer, "1".into()),
+            Punctuator(';'),
+            Keyword("let".into()),
+            Identifier("r".into()),
+            Operator("=".into()),
+            Operator("~/".into()),
+            Embedded("regex", "a+\\/b".into()),
+            Punctuator(';'),
+        ],
+    );
+
+    // Keywords of the mode are recognized when interning
+    let mut interner: Interner = langdef.interner();
+    let stream: TokenStream = Tokenizer::tokenize_interned(&langdef, document, &mut interner).unwrap();
+    assert_eq!(Keyword("SELECT".into()), stream[5].token_type);
+    assert_eq!(Identifier("t".into()), stream[8].token_type);
+
+    assert_eq!(
+        TokenizeError::UnterminatedMode { name: "sql", start: Position::new(0, 1, 1) },
+        Tokenizer::tokenize(&langdef, "sql { SELECT *").unwrap_err(),
+    );
+    assert_eq!(
+        TokenizeError::UnterminatedMode { name: "regex", start: Position::new(6, 1, 7) },
+        Tokenizer::tokenize(&langdef, "r = ~/a+").unwrap_err(),
+    );
 }